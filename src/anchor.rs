@@ -1,8 +1,13 @@
+use crate::table::{ColumnType, OutputFormat, TableWriter, Value};
 use indexmap::IndexMap;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
 
-pub fn anchor_nodes(file_path: &str, max_rank: Option<i32>) -> io::Result<()> {
+pub fn anchor_nodes(
+    file_path: &str,
+    max_rank: Option<i32>,
+    format: OutputFormat,
+) -> io::Result<()> {
     /*
     This function reads a GFA file and for each node emits an annotation of the subset of paths that cross the node
      */
@@ -11,7 +16,13 @@ pub fn anchor_nodes(file_path: &str, max_rank: Option<i32>) -> io::Result<()> {
     let mut segment_replicates: IndexMap<String, i32> = IndexMap::new();
     let mut line: String = String::new();
 
-    println!("# NodeName\tAnchorRank");
+    let mut table: TableWriter = TableWriter::new(
+        format,
+        &[
+            ("NodeName", ColumnType::Text),
+            ("AnchorRank", ColumnType::Integer),
+        ],
+    )?;
     while reader.read_line(&mut line)? > 0 {
         let columns: Vec<&str> = line.split('\t').collect();
         if let Some(first_char) = line.chars().next() {
@@ -44,18 +55,24 @@ pub fn anchor_nodes(file_path: &str, max_rank: Option<i32>) -> io::Result<()> {
         }
         line.clear(); // Clear the line buffer for the next read
     }
-    if max_rank.is_some() {
+    if let Some(max_rank) = max_rank {
         for node in segment_replicates.keys() {
             let obs_rank = *segment_replicates.values().max().unwrap();
-            if segment_replicates[node] >= obs_rank - max_rank.unwrap() {
-                println!("{}\t{}", node, segment_replicates[node]);
+            if segment_replicates[node] >= obs_rank - max_rank {
+                table.write_row(vec![
+                    Value::Text(node.clone()),
+                    Value::Integer(segment_replicates[node] as i64),
+                ])?;
             }
         }
     } else {
         for node in segment_replicates.keys() {
-            println!("{}\t{}", node, segment_replicates[node]);
+            table.write_row(vec![
+                Value::Text(node.clone()),
+                Value::Integer(segment_replicates[node] as i64),
+            ])?;
         }
     }
 
-    Ok(())
+    table.finish()
 }
@@ -0,0 +1,355 @@
+use crate::index_gfa_file::{linear_contig, path_offsets, read_path_offsets, StepOffset};
+use crate::walks::read_lengths;
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+struct Feature {
+    id: String,
+    // Interval of the feature on the reference path, 0-based and half-open
+    start: u64,
+    end: u64,
+    // Bases of the feature on each segment of the reference it covers
+    segments: IndexMap<String, u64>,
+}
+
+pub fn annotate_gfa(
+    file_path: &str,
+    annotation: &str,
+    reference: &str,
+    types: &[String],
+    segments_file: Option<&str>,
+    presence_file: Option<&str>,
+) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    write_annotated(
+        file_path,
+        annotation,
+        reference,
+        types,
+        segments_file,
+        presence_file,
+        &mut writer,
+    )?;
+    writer.flush()
+}
+
+fn write_annotated<W: Write>(
+    file_path: &str,
+    annotation: &str,
+    reference: &str,
+    types: &[String],
+    segments_file: Option<&str>,
+    presence_file: Option<&str>,
+    writer: &mut W,
+) -> io::Result<()> {
+    /*
+    This function reads a GFA file and an annotation (GFF3, or BED if its extension is .bed) on a reference path,
+    and prints the GFA with the features covering each segment in an AN:Z tag of its S-line
+    - features are placed using the offsets of the reference (as given by Offsets), on the contig of its
+      PanSN name; features on other contigs are skipped
+    - types: GFF3 feature types to keep (all if empty)
+    - segments_file: a TSV file with the interval of each segment covered by each feature
+    - presence_file: a TSV file telling for each feature and each path if the path traverses all
+      the segments of the feature (full), some of them (partial) or none (absent), with the fraction
+      of the bases of the feature on the traversed segments
+     */
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
+    let steps: Vec<StepOffset> = read_path_offsets(file_path, &seq_lengths, reference)?;
+    let contig: String = linear_contig(reference);
+    let mut features: Vec<Feature> = read_features(annotation, reference, &contig, types)?;
+
+    let mut segments_writer: Option<BufWriter<File>> = match segments_file {
+        Some(segments_file) => Some(BufWriter::new(File::create(segments_file)?)),
+        None => None,
+    };
+    if let Some(writer) = segments_writer.as_mut() {
+        writeln!(writer, "# NodeName\tFeature\tStart\tEnd")?;
+    }
+    let mut segment_features: HashMap<String, Vec<String>> = HashMap::new();
+    for feature in features.iter_mut() {
+        let first: usize = steps.partition_point(|step| step.end <= feature.start);
+        for step in steps[first..]
+            .iter()
+            .take_while(|step| step.start < feature.end)
+        {
+            let (start, end) = (feature.start.max(step.start), feature.end.min(step.end));
+            // Intervals are given on the forward strand of the segment
+            let (local_start, local_end) = if step.orientation == '+' {
+                (start - step.start, end - step.start)
+            } else {
+                (step.end - end, step.end - start)
+            };
+            if let Some(writer) = segments_writer.as_mut() {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}",
+                    step.node, feature.id, local_start, local_end
+                )?;
+            }
+            *feature.segments.entry(step.node.clone()).or_insert(0) += end - start;
+            let ids: &mut Vec<String> = segment_features.entry(step.node.clone()).or_default();
+            if ids.last() != Some(&feature.id) {
+                ids.push(feature.id.clone());
+            }
+        }
+    }
+    if let Some(writer) = segments_writer.as_mut() {
+        writer.flush()?;
+    }
+
+    let mut presence_writer: Option<BufWriter<File>> = match presence_file {
+        Some(presence_file) => Some(BufWriter::new(File::create(presence_file)?)),
+        None => None,
+    };
+    if let Some(writer) = presence_writer.as_mut() {
+        writeln!(writer, "# Feature\tPath\tStatus\tCoveredFraction")?;
+    }
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line: String = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        let mut columns: Vec<String> = line
+            .trim_end_matches(['\n', '\r'])
+            .split('\t')
+            .map(String::from)
+            .collect();
+        match line.chars().next() {
+            Some('S') => {
+                if let Some(ids) = segment_features.get(&columns[1]) {
+                    columns.retain(|column| !column.starts_with("AN:Z:"));
+                    columns.push(format!("AN:Z:{}", ids.join(",")));
+                }
+            }
+            Some('P') | Some('W') if presence_writer.is_some() => {
+                let columns_str: Vec<&str> = columns.iter().map(String::as_str).collect();
+                if let Some((path_name, offsets)) = path_offsets(&columns_str, &seq_lengths)? {
+                    let traversed: HashSet<&str> =
+                        offsets.iter().map(|step| step.node.as_str()).collect();
+                    let writer: &mut BufWriter<File> = presence_writer.as_mut().unwrap();
+                    for feature in features
+                        .iter()
+                        .filter(|feature| !feature.segments.is_empty())
+                    {
+                        let total: u64 = feature.segments.values().sum();
+                        let covered: u64 = feature
+                            .segments
+                            .iter()
+                            .filter(|(node, _)| traversed.contains(node.as_str()))
+                            .map(|(_, bases)| bases)
+                            .sum();
+                        let status: &str = if covered == total {
+                            "full"
+                        } else if covered == 0 {
+                            "absent"
+                        } else {
+                            "partial"
+                        };
+                        writeln!(
+                            writer,
+                            "{}\t{}\t{}\t{:.4}",
+                            feature.id,
+                            path_name,
+                            status,
+                            covered as f64 / total as f64
+                        )?;
+                    }
+                }
+            }
+            _ => (),
+        }
+        writeln!(writer, "{}", columns.join("\t"))?;
+        line.clear(); // Clear the line buffer for the next read
+    }
+    if let Some(writer) = presence_writer.as_mut() {
+        writer.flush()?;
+    }
+    eprintln!(
+        "{} features annotated on {} segments of {}",
+        features
+            .iter()
+            .filter(|feature| !feature.segments.is_empty())
+            .count(),
+        segment_features.len(),
+        reference
+    );
+
+    Ok(())
+}
+
+fn read_features(
+    annotation: &str,
+    reference: &str,
+    contig: &str,
+    types: &[String],
+) -> io::Result<Vec<Feature>> {
+    /*
+    Reads the features of a GFF3 or BED file on the contig of the reference (or named after the reference),
+    with their interval converted to 0-based half-open coordinates on the reference path
+    GFF3 features are named after their ID or Name attribute, BED features after their name column,
+    and features without name after their type (GFF3) or contig, and their interval
+     */
+    let is_bed: bool = annotation.ends_with(".bed");
+    let file: File = File::open(annotation)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line: String = String::new();
+    let mut features: Vec<Feature> = Vec::new();
+    let mut other_contigs: usize = 0;
+
+    while reader.read_line(&mut line)? > 0 {
+        // Sequences may follow the features of a GFF3 file
+        if line.starts_with("##FASTA") {
+            break;
+        }
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        if line.starts_with(['#', '\n', '\r'])
+            || columns.len() < 3
+            || columns[0] == "track"
+            || columns[0] == "browser"
+        {
+            line.clear();
+            continue;
+        }
+        let number = |index: usize| -> io::Result<u64> {
+            columns
+                .get(index)
+                .and_then(|field| field.parse::<u64>().ok())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Invalid number in column {} of annotation: {}",
+                            index + 1,
+                            line.trim_end()
+                        ),
+                    )
+                })
+        };
+        let (start, end, id): (u64, u64, String) = if is_bed {
+            let (start, end) = (number(1)?, number(2)?);
+            let id: String = match columns.get(3) {
+                Some(name) if *name != "." => name.to_string(),
+                _ => format!("{}:{}-{}", columns[0], start, end),
+            };
+            (start, end, id)
+        } else {
+            if !types.is_empty() && !types.iter().any(|kind| kind == columns[2]) {
+                line.clear();
+                continue;
+            }
+            // GFF3 intervals are 1-based and closed
+            let (start, end) = (number(3)?.saturating_sub(1), number(4)?);
+            let attributes: &str = columns.get(8).copied().unwrap_or_default();
+            let attribute = |key: &str| -> Option<String> {
+                attributes
+                    .split(';')
+                    .find_map(|attribute| attribute.strip_prefix(key))
+                    .map(String::from)
+            };
+            let id: String = attribute("ID=")
+                .or_else(|| attribute("Name="))
+                .unwrap_or_else(|| format!("{}:{}-{}", columns[2], start, end));
+            (start, end, id)
+        };
+        let on_reference: bool = columns[0] == contig || columns[0] == reference;
+        line.clear(); // Clear the line buffer for the next read
+        if !on_reference {
+            other_contigs += 1;
+            continue;
+        }
+        if start < end {
+            features.push(Feature {
+                id,
+                start,
+                end,
+                segments: IndexMap::new(),
+            });
+        }
+    }
+    if other_contigs > 0 {
+        eprintln!(
+            "{} features of {} are not on {} and were skipped",
+            other_contigs, annotation, contig
+        );
+    }
+    features.sort_by_key(|feature| (feature.start, feature.end));
+    Ok(features)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    #[test]
+    fn test_write_annotated() {
+        let directory: std::path::PathBuf = temp_dir("test_write_annotated");
+        let file_path: String = directory.join("graph.gfa").to_string_lossy().to_string();
+        let annotation: String = directory.join("genes.gff3").to_string_lossy().to_string();
+        let segments_file: String = directory.join("segments.tsv").to_string_lossy().to_string();
+        let presence_file: String = directory.join("presence.tsv").to_string_lossy().to_string();
+        std::fs::write(
+            &file_path,
+            "S\t1\tACGT\nS\t2\tGG\nS\t3\tTTT\nS\t4\tA\n\
+             P\tHG1#1#chr1\t1+,2-,3+\t*\nP\tHG2#1#chr1\t1+,4+,3+\t*\n",
+        )
+        .unwrap();
+        // gene1 spans the three segments of the reference, exon1 only the last one
+        std::fs::write(
+            &annotation,
+            "##gff-version 3\n\
+             chr1\t.\tgene\t3\t7\t.\t+\t.\tID=gene1\n\
+             chr1\t.\texon\t8\t9\t.\t+\t.\tName=exon1\n\
+             chr2\t.\tgene\t1\t4\t.\t+\t.\tID=gene2\n",
+        )
+        .unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        write_annotated(
+            &file_path,
+            &annotation,
+            "HG1#1#chr1",
+            &[],
+            Some(&segments_file),
+            Some(&presence_file),
+            &mut output,
+        )
+        .unwrap();
+        let mut genes_only: Vec<u8> = Vec::new();
+        write_annotated(
+            &file_path,
+            &annotation,
+            "HG1#1#chr1",
+            &[String::from("gene")],
+            None,
+            None,
+            &mut genes_only,
+        )
+        .unwrap();
+        let segments: String = std::fs::read_to_string(&segments_file).unwrap();
+        let presence: String = std::fs::read_to_string(&presence_file).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "S\t1\tACGT\tAN:Z:gene1\nS\t2\tGG\tAN:Z:gene1\nS\t3\tTTT\tAN:Z:gene1,exon1\nS\t4\tA\n\
+             P\tHG1#1#chr1\t1+,2-,3+\t*\nP\tHG2#1#chr1\t1+,4+,3+\t*\n"
+        );
+        assert!(String::from_utf8(genes_only)
+            .unwrap()
+            .starts_with("S\t1\tACGT\tAN:Z:gene1\nS\t2\tGG\tAN:Z:gene1\nS\t3\tTTT\tAN:Z:gene1\n"));
+        // Intervals on segment 2, traversed in reverse, are given on its forward strand
+        assert_eq!(
+            segments,
+            "# NodeName\tFeature\tStart\tEnd\n\
+             1\tgene1\t2\t4\n2\tgene1\t0\t2\n3\tgene1\t0\t1\n3\texon1\t1\t3\n"
+        );
+        assert_eq!(
+            presence,
+            "# Feature\tPath\tStatus\tCoveredFraction\n\
+             gene1\tHG1#1#chr1\tfull\t1.0000\nexon1\tHG1#1#chr1\tfull\t1.0000\n\
+             gene1\tHG2#1#chr1\tpartial\t0.6000\nexon1\tHG2#1#chr1\tfull\t1.0000\n"
+        );
+    }
+}
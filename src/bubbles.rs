@@ -0,0 +1,480 @@
+// Superbubble decomposition of the bidirected graph built from L-lines.
+// Each segment is split into two oriented nodes (forward and reverse), so that the bidirected
+// graph becomes a directed graph: a link a+ -> b- gives edges >a -> <b and >b -> <a.
+// Superbubbles are searched from every oriented node, following Onodera et al. (2013).
+use crate::walks::{parse_p_steps, parse_w_steps, read_lengths};
+use indexmap::{IndexMap, IndexSet};
+use petgraph::graphmap::DiGraphMap;
+use petgraph::Direction;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+pub struct Graph {
+    // Segments, in file order, with their sequence length
+    pub segments: IndexMap<String, u64>,
+    // Directed graph on oriented nodes (see oriented_node)
+    pub edges: DiGraphMap<u32, ()>,
+    // Paths and walks, as lists of oriented nodes
+    pub paths: Vec<(String, Vec<u32>)>,
+}
+
+pub struct Bubble {
+    // A bubble goes from a source to a sink (both oriented nodes)
+    pub source: u32,
+    pub sink: u32,
+    // Segments strictly inside the bubble, as segment indices
+    pub inner: Vec<u32>,
+    pub level: usize,
+    pub parent: Option<usize>,
+    pub min_length: u64,
+    pub max_length: u64,
+}
+
+// For each distinct traversal of a bubble (as inner oriented nodes, from source to sink),
+// the paths that take it, as (path index, index of the step entering the bubble)
+pub type Traversals = IndexMap<Vec<u32>, Vec<(usize, usize)>>;
+
+pub fn oriented_node(index: usize, orientation: char) -> u32 {
+    /*
+    Oriented nodes are encoded as 2 * segment index, plus one if read in reverse
+     */
+    (index as u32) * 2 + (orientation == '-' || orientation == '<') as u32
+}
+
+pub fn flip(node: u32) -> u32 {
+    node ^ 1
+}
+
+pub fn is_reverse(node: u32) -> bool {
+    node & 1 == 1
+}
+
+pub fn format_node(graph: &Graph, node: u32) -> String {
+    /*
+    Formats an oriented node as in W-lines, e.g. '>12' or '<12'
+     */
+    let (name, _) = graph.segments.get_index((node / 2) as usize).unwrap();
+    format!("{}{}", if is_reverse(node) { '<' } else { '>' }, name)
+}
+
+pub fn format_walk(graph: &Graph, nodes: &[u32]) -> String {
+    if nodes.is_empty() {
+        return String::from("*");
+    }
+    nodes.iter().map(|node| format_node(graph, *node)).collect()
+}
+
+pub fn node_length(graph: &Graph, node: u32) -> u64 {
+    *graph.segments.get_index((node / 2) as usize).unwrap().1
+}
+
+pub fn load_graph(file_path: &str) -> io::Result<Graph> {
+    /*
+    Given a file path, this function reads the GFA file and returns its segments, the directed graph
+    on oriented nodes built from L-lines, and its paths (P-lines and W-lines)
+    W-lines are named sample#haplotype#seqid
+     */
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut segments: IndexMap<String, u64> = IndexMap::new();
+    let mut links: Vec<(String, char, String, char)> = Vec::new();
+    let mut raw_paths: Vec<(String, Vec<(String, char)>)> = Vec::new();
+    let mut line: String = String::new();
+
+    while reader.read_line(&mut line)? > 0 {
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        if let Some(first_char) = line.chars().next() {
+            if first_char == 'S' {
+                segments.insert(columns[1].to_string(), seq_lengths[columns[1]]);
+            } else if first_char == 'L' {
+                links.push((
+                    columns[1].to_string(),
+                    columns[2].chars().next().unwrap_or('+'),
+                    columns[3].to_string(),
+                    columns[4].chars().next().unwrap_or('+'),
+                ));
+            } else if first_char == 'P' {
+                raw_paths.push((columns[1].to_string(), parse_p_steps(columns[2])?));
+            } else if first_char == 'W' {
+                let path_name: String = format!("{}#{}#{}", columns[1], columns[2], columns[3]);
+                raw_paths.push((path_name, parse_w_steps(columns[6])?));
+            }
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    let index_of = |node: &str| -> io::Result<usize> {
+        segments.get_index_of(node).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Segment not found in graph: {}", node),
+            )
+        })
+    };
+    let mut edges: DiGraphMap<u32, ()> = DiGraphMap::new();
+    for index in 0..segments.len() {
+        edges.add_node(oriented_node(index, '+'));
+        edges.add_node(oriented_node(index, '-'));
+    }
+    for (from, from_orientation, to, to_orientation) in links.iter() {
+        let from_node: u32 = oriented_node(index_of(from)?, *from_orientation);
+        let to_node: u32 = oriented_node(index_of(to)?, *to_orientation);
+        edges.add_edge(from_node, to_node, ());
+        edges.add_edge(flip(to_node), flip(from_node), ());
+    }
+    let mut paths: Vec<(String, Vec<u32>)> = Vec::new();
+    for (path_name, steps) in raw_paths {
+        let mut nodes: Vec<u32> = Vec::new();
+        for (node, orientation) in steps.iter() {
+            nodes.push(oriented_node(index_of(node)?, *orientation));
+        }
+        paths.push((path_name, nodes));
+    }
+
+    Ok(Graph {
+        segments,
+        edges,
+        paths,
+    })
+}
+
+fn superbubble(graph: &Graph, source: u32) -> Option<Bubble> {
+    /*
+    Searches the minimal superbubble starting at the source oriented node
+    Nodes are visited in topological order, a node being visited once all its parents are
+    The bubble is closed when a single node remains to be visited and nothing else was seen
+     */
+    let mut visited: HashSet<u32> = HashSet::new();
+    let mut seen: HashSet<u32> = HashSet::new();
+    let mut order: Vec<u32> = Vec::new();
+    let mut stack: Vec<u32> = vec![source];
+    seen.insert(source);
+
+    while let Some(node) = stack.pop() {
+        visited.insert(node);
+        seen.remove(&node);
+        order.push(node);
+        let mut children = graph
+            .edges
+            .neighbors_directed(node, Direction::Outgoing)
+            .peekable();
+        // A tip: no bubble
+        children.peek()?;
+        for child in children {
+            if child == source {
+                // A cycle including the source: no bubble
+                return None;
+            }
+            seen.insert(child);
+            if graph
+                .edges
+                .neighbors_directed(child, Direction::Incoming)
+                .all(|parent| visited.contains(&parent))
+            {
+                stack.push(child);
+            }
+        }
+        if stack.len() == 1 && seen.len() == 1 && seen.contains(&stack[0]) {
+            let sink: u32 = stack[0];
+            if graph.edges.contains_edge(sink, source) {
+                return None;
+            }
+            let inner: Vec<u32> = order[1..]
+                .iter()
+                .map(|node| node / 2)
+                .collect::<IndexSet<u32>>()
+                .into_iter()
+                .collect();
+            let (min_length, max_length) = traversal_lengths(graph, source, sink, &order);
+            return Some(Bubble {
+                source,
+                sink,
+                inner,
+                level: 0,
+                parent: None,
+                min_length,
+                max_length,
+            });
+        }
+    }
+    None
+}
+
+fn traversal_lengths(graph: &Graph, source: u32, sink: u32, order: &[u32]) -> (u64, u64) {
+    /*
+    Shortest and longest sequence length between source and sink (both excluded)
+    The bubble being acyclic, lengths are propagated along the topological order
+     */
+    let mut lengths: HashMap<u32, (u64, u64)> = HashMap::new();
+    lengths.insert(source, (0, 0));
+    for node in order.iter() {
+        let (min_length, max_length) = lengths[node];
+        let length: u64 = if *node == source {
+            0
+        } else {
+            node_length(graph, *node)
+        };
+        for child in graph.edges.neighbors_directed(*node, Direction::Outgoing) {
+            let entry = lengths.entry(child).or_insert((u64::MAX, 0));
+            entry.0 = entry.0.min(min_length + length);
+            entry.1 = entry.1.max(max_length + length);
+        }
+    }
+    lengths.get(&sink).copied().unwrap_or((0, 0))
+}
+
+pub fn find_bubbles(graph: &Graph) -> Vec<Bubble> {
+    /*
+    Searches superbubbles from every oriented node, and computes their nesting
+    A bubble from s to t is also found from reverse t to reverse s: only one of them is kept
+    Bubbles that are simple links, without inner segments, are not reported
+     */
+    let mut bubbles: Vec<Bubble> = Vec::new();
+    for index in 0..graph.segments.len() {
+        for orientation in ['+', '-'] {
+            let source: u32 = oriented_node(index, orientation);
+            if let Some(bubble) = superbubble(graph, source) {
+                if !bubble.inner.is_empty() && bubble.source < flip(bubble.sink) {
+                    bubbles.push(bubble);
+                }
+            }
+        }
+    }
+
+    // Nesting: the parent of a bubble is the smallest bubble that contains its source and sink
+    let mut containing: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (index, bubble) in bubbles.iter().enumerate() {
+        for segment in bubble.inner.iter() {
+            containing.entry(*segment).or_default().push(index);
+        }
+    }
+    let mut by_size: Vec<usize> = (0..bubbles.len()).collect();
+    by_size.sort_by_key(|index| std::cmp::Reverse(bubbles[*index].inner.len()));
+    for index in by_size {
+        let (source, sink) = (bubbles[index].source / 2, bubbles[index].sink / 2);
+        let closure = |candidate: &Bubble, segment: u32| {
+            candidate.source / 2 == segment
+                || candidate.sink / 2 == segment
+                || candidate.inner.contains(&segment)
+        };
+        let parent: Option<usize> = containing
+            .get(&source)
+            .into_iter()
+            .chain(containing.get(&sink))
+            .flatten()
+            .copied()
+            .filter(|candidate| {
+                *candidate != index
+                    && bubbles[*candidate].inner.len() > bubbles[index].inner.len()
+                    && closure(&bubbles[*candidate], source)
+                    && closure(&bubbles[*candidate], sink)
+            })
+            .min_by_key(|candidate| bubbles[*candidate].inner.len());
+        if let Some(parent) = parent {
+            bubbles[index].level = bubbles[parent].level + 1;
+            bubbles[index].parent = Some(parent);
+        }
+    }
+    bubbles
+}
+
+pub fn path_traversals(graph: &Graph, bubbles: &[Bubble]) -> Vec<Traversals> {
+    /*
+    For each bubble, the distinct traversals taken by paths, and the paths that take them
+    Paths can cross a bubble in reverse, from reverse sink to reverse source: their traversal
+    is then reversed so that all traversals read from source to sink
+     */
+    let mut traversals: Vec<Traversals> = (0..bubbles.len()).map(|_| IndexMap::new()).collect();
+    let mut entries: HashMap<u32, (usize, bool)> = HashMap::new();
+    for (index, bubble) in bubbles.iter().enumerate() {
+        entries.insert(bubble.source, (index, false));
+        entries.insert(flip(bubble.sink), (index, true));
+    }
+    for (path_index, (_, nodes)) in graph.paths.iter().enumerate() {
+        for (step, node) in nodes.iter().enumerate() {
+            if let Some((index, reverse)) = entries.get(node) {
+                let bubble: &Bubble = &bubbles[*index];
+                let exit: u32 = if *reverse {
+                    flip(bubble.source)
+                } else {
+                    bubble.sink
+                };
+                if let Some(length) = nodes[step + 1..].iter().position(|n| *n == exit) {
+                    let mut traversal: Vec<u32> = nodes[step + 1..step + 1 + length].to_vec();
+                    if *reverse {
+                        traversal = traversal.iter().rev().map(|n| flip(*n)).collect();
+                    }
+                    traversals[*index]
+                        .entry(traversal)
+                        .or_default()
+                        .push((path_index, step));
+                }
+            }
+        }
+    }
+    traversals
+}
+
+pub fn bubbles_gfa(file_path: &str, traversals_file: Option<&str>) -> io::Result<()> {
+    /*
+    This function reads a GFA file and prints, for each superbubble, its source and sink,
+    its nesting level and parent bubble, its number of inner segments, the range of lengths
+    of its traversals, and the number of distinct traversals taken by paths
+    If a file is given, the paths taking each traversal are written to it
+     */
+    let graph: Graph = load_graph(file_path)?;
+    let bubbles: Vec<Bubble> = find_bubbles(&graph);
+    let traversals: Vec<Traversals> = path_traversals(&graph, &bubbles);
+    eprintln!("Found {} bubbles in {}", bubbles.len(), file_path);
+
+    println!("# BubbleID\tSource\tSink\tLevel\tParent\tInnerSegments\tMinLength\tMaxLength\tTraversals\tPaths");
+    for (index, bubble) in bubbles.iter().enumerate() {
+        let paths: HashSet<usize> = traversals[index]
+            .values()
+            .flatten()
+            .map(|(path, _)| *path)
+            .collect();
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            index + 1,
+            format_node(&graph, bubble.source),
+            format_node(&graph, bubble.sink),
+            bubble.level,
+            bubble
+                .parent
+                .map(|parent| (parent + 1).to_string())
+                .unwrap_or(String::from("*")),
+            bubble.inner.len(),
+            bubble.min_length,
+            bubble.max_length,
+            traversals[index].len(),
+            paths.len()
+        );
+    }
+
+    if let Some(traversals_file) = traversals_file {
+        let output_file: File = File::create(traversals_file)?;
+        let mut file_writer: BufWriter<File> = BufWriter::new(output_file);
+        writeln!(file_writer, "# BubbleID\tTraversal\tLength\tPaths")?;
+        for (index, bubble_traversals) in traversals.iter().enumerate() {
+            for (traversal, occurences) in bubble_traversals.iter() {
+                let length: u64 = traversal
+                    .iter()
+                    .map(|node| node_length(&graph, *node))
+                    .sum();
+                let mut path_names: Vec<&str> = occurences
+                    .iter()
+                    .map(|(path, _)| graph.paths[*path].0.as_str())
+                    .collect();
+                path_names.dedup();
+                writeln!(
+                    file_writer,
+                    "{}\t{}\t{}\t{}",
+                    index + 1,
+                    format_walk(&graph, traversal),
+                    length,
+                    path_names.join(",")
+                )?;
+            }
+        }
+        file_writer.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_file;
+
+    fn build_graph(segments: &[(&str, u64)], links: &[(&str, char, &str, char)]) -> Graph {
+        let segments: IndexMap<String, u64> = segments
+            .iter()
+            .map(|(name, length)| (name.to_string(), *length))
+            .collect();
+        let mut edges: DiGraphMap<u32, ()> = DiGraphMap::new();
+        for (from, from_orientation, to, to_orientation) in links.iter() {
+            let from_node: u32 =
+                oriented_node(segments.get_index_of(*from).unwrap(), *from_orientation);
+            let to_node: u32 = oriented_node(segments.get_index_of(*to).unwrap(), *to_orientation);
+            edges.add_edge(from_node, to_node, ());
+            edges.add_edge(flip(to_node), flip(from_node), ());
+        }
+        Graph {
+            segments,
+            edges,
+            paths: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_nested_bubbles() {
+        // 1 -> (2 -> (3 | 4) -> 5 | 6) -> 7
+        let mut graph: Graph = build_graph(
+            &[
+                ("1", 1),
+                ("2", 1),
+                ("3", 2),
+                ("4", 5),
+                ("5", 1),
+                ("6", 3),
+                ("7", 1),
+            ],
+            &[
+                ("1", '+', "2", '+'),
+                ("2", '+', "3", '+'),
+                ("2", '+', "4", '+'),
+                ("3", '+', "5", '+'),
+                ("4", '+', "5", '+'),
+                ("5", '+', "7", '+'),
+                ("1", '+', "6", '+'),
+                ("6", '+', "7", '+'),
+            ],
+        );
+        let bubbles: Vec<Bubble> = find_bubbles(&graph);
+        assert_eq!(bubbles.len(), 2);
+        let outer: &Bubble = bubbles.iter().find(|b| b.level == 0).unwrap();
+        let inner: &Bubble = bubbles.iter().find(|b| b.level == 1).unwrap();
+        assert_eq!(format_node(&graph, outer.source), ">1");
+        assert_eq!(format_node(&graph, outer.sink), ">7");
+        assert_eq!(outer.inner.len(), 5);
+        assert_eq!((outer.min_length, outer.max_length), (3, 7));
+        assert_eq!(format_node(&graph, inner.source), ">2");
+        assert_eq!((inner.min_length, inner.max_length), (2, 5));
+
+        // A path crossing the inner bubble in reverse
+        graph.paths.push((String::from("x"), vec![13, 9, 7, 3, 1]));
+        let traversals: Vec<Traversals> = path_traversals(&graph, &bubbles);
+        let inner_index: usize = bubbles.iter().position(|b| b.level == 1).unwrap();
+        let (traversal, occurences) = traversals[inner_index].get_index(0).unwrap();
+        assert_eq!(format_walk(&graph, traversal), ">4");
+        assert_eq!(occurences, &vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_load_graph() {
+        let file_path: String = temp_file("test_load_graph.gfa");
+        std::fs::write(
+            &file_path,
+            "S\t1\tACGT\nS\t2\t*\tLN:i:7\nS\t3\tG\n\
+             L\t1\t+\t2\t-\t0M\nL\t2\t-\t3\t+\t0M\n\
+             P\tx\t1+,2-,3+\t*\n",
+        )
+        .unwrap();
+        let graph: Graph = load_graph(&file_path).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+
+        // Segment 2 has no sequence, its length comes from its LN tag
+        assert_eq!(
+            graph.segments.values().copied().collect::<Vec<u64>>(),
+            vec![4, 7, 1]
+        );
+        assert!(graph
+            .edges
+            .contains_edge(oriented_node(0, '+'), oriented_node(1, '-')));
+        assert_eq!(format_walk(&graph, &graph.paths[0].1), ">1<2>3");
+    }
+}
@@ -0,0 +1,327 @@
+use crate::sequence::reverse_complement;
+use crate::walks::{parse_p_steps, parse_w_steps};
+use indexmap::IndexMap;
+use std::collections::HashSet;
+use std::io;
+
+// An end of a segment: its index, and whether it is the end (right side) of its sequence
+type End = (usize, bool);
+
+struct Chain {
+    // Segment kept as the ID of the chain
+    head: usize,
+    // Segments of the chain with their orientation, the chain being read with its head forward
+    nodes: Vec<(usize, bool)>,
+}
+
+pub fn compact_lines(lines: Vec<String>) -> io::Result<Vec<String>> {
+    /*
+    Compacts the topology of a graph given as GFA lines (without trailing newline)
+    - tips: segments traversed by no path and with a side without links are removed, iteratively
+    - chains: two segment ends linked only together (with a 0M or * overlap), where no path
+      starts or ends, are merged; a chain keeps the ID of its first segment in the file
+    Merged segments lose their tags, links inside chains are removed and paths are rewritten
+     */
+    let mut segments: IndexMap<String, String> = IndexMap::new();
+    for line in lines.iter().filter(|line| line.starts_with('S')) {
+        let columns: Vec<&str> = line.split('\t').collect();
+        segments.insert(columns[1].to_string(), columns[2].to_string());
+    }
+    let segment_index = |node: &str| -> io::Result<usize> {
+        segments.get_index_of(node).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Segment not found in graph: {}", node),
+            )
+        })
+    };
+
+    // Links between ends, and ends where a path starts or stops
+    let mut neighbors: Vec<[Vec<(End, bool)>; 2]> = vec![[Vec::new(), Vec::new()]; segments.len()];
+    let mut traversed: Vec<bool> = vec![false; segments.len()];
+    let mut boundaries: HashSet<End> = HashSet::new();
+    for line in lines.iter() {
+        let columns: Vec<&str> = line.split('\t').collect();
+        match columns[0] {
+            "L" => {
+                let from: End = (segment_index(columns[1])?, columns[2] == "+");
+                let to: End = (segment_index(columns[3])?, columns[4] == "-");
+                // Only links without overlap can be merged
+                let mergeable: bool = columns.get(5).is_none_or(|o| *o == "0M" || *o == "*");
+                neighbors[from.0][from.1 as usize].push((to, mergeable));
+                if from != to {
+                    neighbors[to.0][to.1 as usize].push((from, mergeable));
+                }
+            }
+            "P" | "W" => {
+                let steps: Vec<(usize, bool)> = path_steps(&columns, &segment_index)?;
+                for (index, _) in steps.iter() {
+                    traversed[*index] = true;
+                }
+                // A path enters its first step from its start, and leaves its last step by its end
+                if let (Some(first), Some(last)) = (steps.first(), steps.last()) {
+                    boundaries.insert((first.0, !first.1));
+                    boundaries.insert((last.0, last.1));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Tips are removed until none is left
+    let mut removed: Vec<bool> = vec![false; segments.len()];
+    let mut stack: Vec<usize> = (0..segments.len()).collect();
+    while let Some(index) = stack.pop() {
+        if removed[index] || traversed[index] {
+            continue;
+        }
+        let is_tip: bool = neighbors[index].iter().any(|side| {
+            side.iter()
+                .all(|((other, _), _)| removed[*other] || *other == index)
+        });
+        if is_tip {
+            removed[index] = true;
+            for side in neighbors[index].iter() {
+                stack.extend(side.iter().map(|((other, _), _)| *other));
+            }
+        }
+    }
+    let live_neighbors = |end: End| -> Vec<(End, bool)> {
+        neighbors[end.0][end.1 as usize]
+            .iter()
+            .filter(|((other, _), _)| !removed[*other])
+            .copied()
+            .collect()
+    };
+    // The end linked to this one, if both are only linked together and can be merged
+    let merge_partner = |end: End| -> Option<End> {
+        let links: Vec<(End, bool)> = live_neighbors(end);
+        let [(other, true)] = links[..] else {
+            return None;
+        };
+        if other.0 == end.0 || boundaries.contains(&end) || boundaries.contains(&other) {
+            return None;
+        }
+        let back_links: Vec<(End, bool)> = live_neighbors(other);
+        (back_links.len() == 1).then_some(other)
+    };
+
+    // Chains are built from their first segment in the file, extending both ways
+    let mut chains: Vec<Chain> = Vec::new();
+    // Chain of each segment, with its position and orientation in the chain
+    let mut chain_of: Vec<Option<(usize, usize, bool)>> = vec![None; segments.len()];
+    for start in 0..segments.len() {
+        if removed[start] || chain_of[start].is_some() {
+            continue;
+        }
+        let mut in_chain: HashSet<usize> = HashSet::from([start]);
+        let mut extend = |forward_start: bool| -> Vec<(usize, bool)> {
+            let mut extension: Vec<(usize, bool)> = Vec::new();
+            let mut current: (usize, bool) = (start, forward_start);
+            // The end of an oriented segment is its right side when forward
+            while let Some((next, side)) = merge_partner((current.0, current.1)) {
+                if !in_chain.insert(next) {
+                    break;
+                }
+                // Entering by the left side means reading the segment forward
+                current = (next, !side);
+                extension.push(current);
+            }
+            extension
+        };
+        let right: Vec<(usize, bool)> = extend(true);
+        let left: Vec<(usize, bool)> = extend(false);
+        let mut nodes: Vec<(usize, bool)> = left
+            .into_iter()
+            .rev()
+            .map(|(index, forward)| (index, !forward))
+            .collect();
+        nodes.push((start, true));
+        nodes.extend(right);
+        let chain_index: usize = chains.len();
+        for (position, (index, forward)) in nodes.iter().enumerate() {
+            chain_of[*index] = Some((chain_index, position, *forward));
+        }
+        chains.push(Chain { head: start, nodes });
+    }
+
+    // Lines are rewritten, merged segments being written where their head was
+    let mut output: Vec<String> = Vec::new();
+    let mut written_links: HashSet<(usize, bool, usize, bool)> = HashSet::new();
+    for line in lines.iter() {
+        let mut columns: Vec<String> = line.split('\t').map(String::from).collect();
+        match columns[0].as_str() {
+            "S" => {
+                let index: usize = segment_index(&columns[1])?;
+                if removed[index] {
+                    continue;
+                }
+                let (chain_index, _, _) = chain_of[index].unwrap();
+                let chain: &Chain = &chains[chain_index];
+                if chain.head != index {
+                    continue;
+                }
+                if chain.nodes.len() > 1 {
+                    let sequence: String = chain
+                        .nodes
+                        .iter()
+                        .map(|(node, forward)| {
+                            let (_, sequence) = segments.get_index(*node).unwrap();
+                            if *forward {
+                                sequence.clone()
+                            } else {
+                                reverse_complement(sequence)
+                            }
+                        })
+                        .collect();
+                    columns.truncate(3);
+                    columns[2] = sequence;
+                }
+            }
+            "L" => {
+                let from: (usize, bool) = (segment_index(&columns[1])?, columns[2] == "+");
+                let to: (usize, bool) = (segment_index(&columns[3])?, columns[4] == "+");
+                if removed[from.0] || removed[to.0] {
+                    continue;
+                }
+                let (Some(from), Some(to)) = (
+                    chain_end(&chains, &chain_of, from, true),
+                    chain_end(&chains, &chain_of, to, false),
+                ) else {
+                    // Links inside a chain disappear with the merge
+                    continue;
+                };
+                let reverse: (usize, bool, usize, bool) = (to.0, !to.1, from.0, !from.1);
+                if written_links.contains(&reverse)
+                    || !written_links.insert((from.0, from.1, to.0, to.1))
+                {
+                    continue;
+                }
+                columns[1] = segments.get_index(from.0).unwrap().0.clone();
+                columns[2] = if from.1 { "+" } else { "-" }.to_string();
+                columns[3] = segments.get_index(to.0).unwrap().0.clone();
+                columns[4] = if to.1 { "+" } else { "-" }.to_string();
+            }
+            "P" | "W" => {
+                let columns_str: Vec<&str> = line.split('\t').collect();
+                let mut steps: Vec<(usize, bool)> = Vec::new();
+                for step in path_steps(&columns_str, &segment_index)? {
+                    // Only the step entering a chain is kept
+                    let (chain_index, position, forward) = chain_of[step.0].unwrap();
+                    let chain: &Chain = &chains[chain_index];
+                    let forward_in_chain: bool = step.1 == forward;
+                    let entry: usize = if forward_in_chain {
+                        0
+                    } else {
+                        chain.nodes.len() - 1
+                    };
+                    if position == entry {
+                        steps.push((chain.head, forward_in_chain));
+                    }
+                }
+                if columns[0] == "P" {
+                    let path: Vec<String> = steps
+                        .iter()
+                        .map(|(index, forward)| {
+                            format!(
+                                "{}{}",
+                                segments.get_index(*index).unwrap().0,
+                                if *forward { '+' } else { '-' }
+                            )
+                        })
+                        .collect();
+                    columns[2] = path.join(",");
+                } else {
+                    columns[6] = steps
+                        .iter()
+                        .map(|(index, forward)| {
+                            format!(
+                                "{}{}",
+                                if *forward { '>' } else { '<' },
+                                segments.get_index(*index).unwrap().0
+                            )
+                        })
+                        .collect();
+                }
+            }
+            _ => (),
+        }
+        output.push(columns.join("\t"));
+    }
+    let merged: usize = chains.iter().map(|chain| chain.nodes.len() - 1).sum();
+    eprintln!(
+        "Compaction removed {} tips and merged {} segments into chains",
+        removed.iter().filter(|removed| **removed).count(),
+        merged
+    );
+    Ok(output)
+}
+
+fn chain_end(
+    chains: &[Chain],
+    chain_of: &[Option<(usize, usize, bool)>],
+    step: (usize, bool),
+    leaving: bool,
+) -> Option<(usize, bool)> {
+    /*
+    Translates an oriented segment on a link into its chain (head and orientation)
+    The segment must be the last of the chain when leaving it, or the first when entering it
+     */
+    let (chain_index, position, forward) = chain_of[step.0]?;
+    let chain: &Chain = &chains[chain_index];
+    let forward_in_chain: bool = step.1 == forward;
+    let expected: usize = if forward_in_chain == leaving {
+        chain.nodes.len() - 1
+    } else {
+        0
+    };
+    (position == expected).then_some((chain.head, forward_in_chain))
+}
+
+fn path_steps(
+    columns: &[&str],
+    segment_index: &dyn Fn(&str) -> io::Result<usize>,
+) -> io::Result<Vec<(usize, bool)>> {
+    /*
+    Steps of a P-line or a W-line, as segment indices and forward orientation
+     */
+    let steps: Vec<(String, char)> = if columns[0] == "P" {
+        parse_p_steps(columns[2])?
+    } else {
+        parse_w_steps(columns[6])?
+    };
+    steps
+        .into_iter()
+        .map(|(node, orientation)| {
+            Ok((
+                segment_index(&node)?,
+                orientation == '+' || orientation == '>',
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_lines() {
+        let lines: Vec<String> = [
+            "S\t1\tAC",
+            "S\t2\tGT",
+            "S\t3\tTT",
+            "S\t4\tG",
+            "L\t1\t+\t2\t-\t0M",
+            "L\t2\t-\t3\t+\t0M",
+            "L\t3\t+\t4\t+\t0M",
+            "P\tx\t1+,2-,3+\t*",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+        let compacted: Vec<String> = compact_lines(lines).unwrap();
+        // Segment 4 is a tip, and 1, 2 and 3 are merged into a single segment
+        assert_eq!(compacted, vec!["S\t1\tACACTT", "P\tx\t1+\t*"]);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::walks::{parse_p_steps, parse_w_steps, split_pansn};
+use indexmap::IndexMap;
+use petgraph::unionfind::UnionFind;
+use std::collections::{HashMap, HashSet};
+use std::fs::{create_dir_all, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+struct PathInfo {
+    name: String,
+    contig: String,
+    sample: String,
+    nodes: Vec<String>,
+}
+
+struct Component {
+    name: String,
+    segments: usize,
+    length: u64,
+    paths: Vec<String>,
+}
+
+pub fn split_components(
+    file_path: &str,
+    reference_sample: Option<&str>,
+    output_dir: Option<&str>,
+    selected_paths: &[String],
+) -> io::Result<()> {
+    /*
+    This function reads a GFA file and computes its connected components, from L-lines and paths
+    Each component is reported with its number of segments, its length and the paths it contains
+    Components are named after their dominant contig: the contig (from PanSN names or W-lines)
+    whose paths span the most bp in the component, only considering paths of the reference sample if given
+    Names are used as file names, characters other than letters, digits and -_.# being replaced by '_'
+    - output_dir: directory where each component is written to <name>.gfa, H-lines being copied to all
+    - selected_paths: only components containing one of these paths are written
+     */
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line: String = String::new();
+
+    let mut segments: IndexMap<String, u64> = IndexMap::new();
+    // Unions are only computed once all segments are known, as paths may come before S-lines
+    let mut unions: Vec<(String, String)> = Vec::new();
+    let mut paths: Vec<PathInfo> = Vec::new();
+
+    while reader.read_line(&mut line)? > 0 {
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        match line.chars().next() {
+            Some('S') => {
+                segments.insert(columns[1].to_string(), columns[2].len() as u64);
+            }
+            Some('L') => unions.push((columns[1].to_string(), columns[3].to_string())),
+            Some('P') => {
+                let (sample, contig) = match split_pansn(columns[1]) {
+                    Some(pansn) => (pansn.sample.to_string(), pansn.contig.to_string()),
+                    None => (columns[1].to_string(), columns[1].to_string()),
+                };
+                let nodes: Vec<String> = parse_p_steps(columns[2])?
+                    .into_iter()
+                    .map(|(node, _)| node)
+                    .collect();
+                paths.push(PathInfo {
+                    name: columns[1].to_string(),
+                    contig,
+                    sample,
+                    nodes,
+                });
+            }
+            Some('W') => {
+                let nodes: Vec<String> = parse_w_steps(columns[6])?
+                    .into_iter()
+                    .map(|(node, _)| node)
+                    .collect();
+                paths.push(PathInfo {
+                    name: format!("{}#{}#{}", columns[1], columns[2], columns[3]),
+                    contig: columns[3].to_string(),
+                    sample: columns[1].to_string(),
+                    nodes,
+                });
+            }
+            _ => (),
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    let mut union_find: UnionFind<usize> = UnionFind::new(segments.len());
+    let segment_index = |node: &str| -> io::Result<usize> {
+        segments.get_index_of(node).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Segment not found in graph: {}", node),
+            )
+        })
+    };
+    for (from, to) in unions.iter() {
+        union_find.union(segment_index(from)?, segment_index(to)?);
+    }
+    for path in paths.iter() {
+        for window in path.nodes.windows(2) {
+            union_find.union(segment_index(&window[0])?, segment_index(&window[1])?);
+        }
+    }
+
+    // Components are numbered in the order of their first segment in the file
+    let mut component_of_root: HashMap<usize, usize> = HashMap::new();
+    let mut component_of_segment: Vec<usize> = Vec::with_capacity(segments.len());
+    let mut components: Vec<Component> = Vec::new();
+    for (index, length) in segments.values().enumerate() {
+        let root: usize = union_find.find(index);
+        let component: usize = *component_of_root.entry(root).or_insert_with(|| {
+            components.push(Component {
+                name: String::new(),
+                segments: 0,
+                length: 0,
+                paths: Vec::new(),
+            });
+            components.len() - 1
+        });
+        components[component].segments += 1;
+        components[component].length += length;
+        component_of_segment.push(component);
+    }
+
+    // Length spanned by each contig in each component, to find the dominant one
+    let mut contig_lengths: Vec<IndexMap<String, u64>> = vec![IndexMap::new(); components.len()];
+    let mut component_of_path: HashMap<String, usize> = HashMap::new();
+    for path in paths.iter() {
+        let Some(first_node) = path.nodes.first() else {
+            continue;
+        };
+        let component: usize = component_of_segment[segment_index(first_node)?];
+        components[component].paths.push(path.name.clone());
+        component_of_path.insert(path.name.clone(), component);
+        if reference_sample.is_none_or(|reference_sample| reference_sample == path.sample) {
+            let length: u64 = path.nodes.iter().map(|node| segments[node.as_str()]).sum();
+            *contig_lengths[component]
+                .entry(path.contig.clone())
+                .or_default() += length;
+        }
+    }
+    let dominant_contigs: Vec<String> = contig_lengths
+        .iter()
+        .enumerate()
+        .map(|(index, contig_lengths)| {
+            contig_lengths
+                .iter()
+                .max_by_key(|(_, length)| **length)
+                .map(|(contig, _)| file_name(contig))
+                .unwrap_or_else(|| format!("component_{}", index + 1))
+        })
+        .collect();
+    for (component, name) in components.iter_mut().zip(unique_names(dominant_contigs)) {
+        component.name = name;
+    }
+
+    println!("# Component\tSegments\tLength\tPaths");
+    for component in components.iter() {
+        println!(
+            "{}\t{}\t{}\t{}",
+            component.name,
+            component.segments,
+            component.length,
+            if component.paths.is_empty() {
+                String::from("*")
+            } else {
+                component.paths.join(",")
+            }
+        );
+    }
+
+    let Some(output_dir) = output_dir else {
+        return Ok(());
+    };
+    let mut selected: Vec<bool> = vec![selected_paths.is_empty(); components.len()];
+    for path_name in selected_paths.iter() {
+        match component_of_path.get(path_name) {
+            Some(component) => selected[*component] = true,
+            None => {
+                eprintln!("Path {} not found in graph", path_name);
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Path not found in graph: {}", path_name),
+                ));
+            }
+        }
+    }
+    create_dir_all(output_dir)?;
+    let mut writers: Vec<Option<BufWriter<File>>> = Vec::with_capacity(components.len());
+    for (index, component) in components.iter().enumerate() {
+        if selected[index] {
+            let output_file: File =
+                File::create(Path::new(output_dir).join(format!("{}.gfa", component.name)))?;
+            writers.push(Some(BufWriter::new(output_file)));
+        } else {
+            writers.push(None);
+        }
+    }
+
+    // Second pass, each line goes to the component of the first segment it refers to
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut path_index: usize = 0;
+    while reader.read_line(&mut line)? > 0 {
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        let component: Option<usize> = match line.chars().next() {
+            Some('H') => {
+                for writer in writers.iter_mut().flatten() {
+                    writer.write_all(line.as_bytes())?;
+                }
+                None
+            }
+            Some('P') | Some('W') => {
+                path_index += 1;
+                paths[path_index - 1]
+                    .nodes
+                    .first()
+                    .map(|node| component_of_segment[segments.get_index_of(node).unwrap()])
+            }
+            Some('#') | None => None,
+            _ => columns
+                .get(1)
+                .and_then(|node| segments.get_index_of(*node))
+                .map(|index| component_of_segment[index]),
+        };
+        if let Some(Some(writer)) = component.map(|component| &mut writers[component]) {
+            writer.write_all(line.as_bytes())?;
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+    for writer in writers.iter_mut().flatten() {
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn file_name(name: &str) -> String {
+    /*
+    Makes a contig name safe to use as a file name in the output directory:
+    path separators and other special characters are replaced, as well as a leading '.'
+     */
+    name.chars()
+        .enumerate()
+        .map(|(index, character)| match character {
+            '.' if index == 0 => '_',
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | '#' => character,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn unique_names(names: Vec<String>) -> Vec<String> {
+    /*
+    Several components may share the same dominant contig: the later ones get a _2, _3... suffix,
+    skipping suffixed names that are already the name of another component
+     */
+    let taken: HashSet<String> = names.iter().cloned().collect();
+    let mut used: HashSet<String> = HashSet::new();
+    names
+        .into_iter()
+        .map(|name| {
+            if used.insert(name.clone()) {
+                return name;
+            }
+            let mut count: usize = 2;
+            loop {
+                let candidate: String = format!("{}_{}", name, count);
+                if !taken.contains(&candidate) && used.insert(candidate.clone()) {
+                    return candidate;
+                }
+                count += 1;
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    #[test]
+    fn test_unique_names() {
+        let names: Vec<String> = ["chr1", "chr1", "chr1_2", "chr1", "component_5"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        assert_eq!(
+            unique_names(names),
+            vec!["chr1", "chr1_3", "chr1_2", "chr1_4", "component_5"]
+        );
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("chr1.1"), "chr1.1");
+        assert_eq!(file_name("../etc/passwd"), "_._etc_passwd");
+        assert_eq!(file_name(".."), "_.");
+        assert_eq!(file_name("chrUn gl000220 v1"), "chrUn_gl000220_v1");
+    }
+
+    #[test]
+    fn test_split_components() {
+        let directory: std::path::PathBuf = temp_dir("test_split_components");
+        let file_path: String = directory.join("graph.gfa").to_string_lossy().to_string();
+        let output_dir: String = directory.join("components").to_string_lossy().to_string();
+        // Two components: 1-2 (linked) and 3-4 (joined by a path only), named after their contigs
+        std::fs::write(
+            &file_path,
+            "H\tVN:Z:1.1\nS\t1\tACGT\nS\t2\tA\nS\t3\tGG\nS\t4\tTTT\nL\t1\t+\t2\t+\t0M\n\
+             P\tHG1#1#chr1\t1+,2+\t*\nW\tHG1\t1\tchr2\t0\t5\t>3>4\nP\tHG2#1#chr1\t3+\t*\n",
+        )
+        .unwrap();
+        let result: io::Result<()> = split_components(
+            &file_path,
+            Some("HG1"),
+            Some(&output_dir),
+            &[String::from("HG1#1#chr2")],
+        );
+        let chr1: bool = Path::new(&output_dir).join("chr1.gfa").exists();
+        let chr2: String =
+            std::fs::read_to_string(Path::new(&output_dir).join("chr2.gfa")).unwrap();
+        let missing_path: io::Result<()> = split_components(
+            &file_path,
+            None,
+            Some(&output_dir),
+            &[String::from("HG3#1#chr1")],
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(result.is_ok());
+        // Only the component of the selected path is written
+        assert!(!chr1);
+        assert_eq!(
+            chr2,
+            "H\tVN:Z:1.1\nS\t3\tGG\nS\t4\tTTT\nW\tHG1\t1\tchr2\t0\t5\t>3>4\nP\tHG2#1#chr1\t3+\t*\n"
+        );
+        assert!(missing_path.is_err());
+    }
+}
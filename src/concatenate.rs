@@ -1,94 +1,408 @@
-use std::cmp::max;
-use std::collections::HashMap;
+use crate::sequence::reverse_complement;
+use crate::walks::{parse_p_steps, parse_w_steps};
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
-use std::process::exit;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
 
-pub fn concat_graphs(file_path: &str, graph_to_append: &str) -> io::Result<()> {
+type Link = (String, char, String, char);
+
+pub fn concat_graphs(
+    file_path: &str,
+    graphs_to_append: &[String],
+    output_mapping: Option<&str>,
+    merge: bool,
+) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    write_graphs(
+        file_path,
+        graphs_to_append,
+        output_mapping,
+        merge,
+        &mut writer,
+    )?;
+    writer.flush()
+}
+
+fn write_graphs<W: Write>(
+    file_path: &str,
+    graphs_to_append: &[String],
+    output_mapping: Option<&str>,
+    merge: bool,
+    writer: &mut W,
+) -> io::Result<()> {
     /*
-    This function reads a GFA file and prints each line with modified IDs, and outputs to other file the node mapping
+    This function reads several GFA files and prints them as a single graph, keeping all tags
+    - segments of the first graph keep their IDs, segments of the next graphs get new numeric IDs
+      that are not used by previous graphs
+    - H-lines are merged into a single one: the highest VN is kept (1.1 if there are W-lines),
+      and other tags are kept from the first graph that defines them
+    - output_mapping: a TSV file with the graph, the old ID and the new ID of each segment
+    - path names (PanSN names for W-lines) must be unique across graphs, or nothing is printed
+    - merge: segments with identical sequence and identical neighborhood (sequences of
+      their neighbors on each side) are unified across graphs, duplicated links being removed
      */
-    let file_a: File = File::open(file_path)?;
-    let mut reader_a: BufReader<File> = BufReader::new(file_a);
-    let mut line: String = String::new();
-    let mut max_node_id: u32 = 0;
-
-    // We seek for the maximum node id in the first graph
-    while reader_a.read_line(&mut line)? > 0 {
-        let columns: Vec<&str> = line.split('\t').collect();
-        if let Some(first_char) = line.chars().next() {
-            if first_char == 'S' {
-                // Rename nodes given a linear contiguous attribution
-                let node_name: u32 = columns[1].parse::<u32>().unwrap();
-                max_node_id = max(node_name, max_node_id);
+    let graphs: Vec<&str> = std::iter::once(file_path)
+        .chain(graphs_to_append.iter().map(String::as_str))
+        .collect();
+    let mut header_tags: IndexMap<String, String> = IndexMap::new();
+    let mut has_walks: bool = false;
+    let mut used_ids: HashSet<String> = HashSet::new();
+    let mut next_id: u64 = 1;
+    let mut mappings: Vec<IndexMap<String, String>> = Vec::new();
+    let mut merged: Vec<HashSet<String>> = Vec::new();
+    // Signature of each segment in merge mode, with the graph and the ID it was given
+    let mut signatures: HashMap<String, (usize, String)> = HashMap::new();
+    // Graph in which each path name was first seen
+    let mut path_graphs: HashMap<String, &str> = HashMap::new();
+
+    // First pass on each graph, to gather headers and attribute IDs to segments
+    for (graph_index, graph) in graphs.iter().enumerate() {
+        let file: File = File::open(graph)?;
+        let mut reader: BufReader<File> = BufReader::new(file);
+        let mut line: String = String::new();
+        let mut segments: IndexMap<String, String> = IndexMap::new();
+        let mut links: Vec<Link> = Vec::new();
+
+        while reader.read_line(&mut line)? > 0 {
+            let columns: Vec<&str> = line.trim_end().split('\t').collect();
+            match line.chars().next() {
+                Some('H') => {
+                    for tag in columns.iter().skip(1) {
+                        merge_header_tag(&mut header_tags, tag, graph);
+                    }
+                }
+                Some('S') => {
+                    // Sequences are only needed to compare segments when merging
+                    let sequence: &str = if merge { columns[2] } else { "" };
+                    segments.insert(columns[1].to_string(), sequence.to_string());
+                }
+                Some('L') if merge => links.push((
+                    columns[1].to_string(),
+                    columns[2].chars().next().unwrap_or('+'),
+                    columns[3].to_string(),
+                    columns[4].chars().next().unwrap_or('+'),
+                )),
+                Some('P') | Some('W') => {
+                    let path_name: String = if line.starts_with('W') {
+                        has_walks = true;
+                        format!("{}#{}#{}", columns[1], columns[2], columns[3])
+                    } else {
+                        columns[1].to_string()
+                    };
+                    if let Some(other_graph) = path_graphs.insert(path_name.clone(), graph) {
+                        eprintln!(
+                            "Path {} of {} is already in {}",
+                            path_name, graph, other_graph
+                        );
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Duplicate path name: {}", path_name),
+                        ));
+                    }
+                }
+                _ => (),
+            }
+            line.clear(); // Clear the line buffer for the next read
+        }
+
+        let graph_signatures: HashMap<String, String> = if merge {
+            neighborhood_signatures(&segments, &links)
+        } else {
+            HashMap::new()
+        };
+        let mut mapping: IndexMap<String, String> = IndexMap::new();
+        let mut merged_segments: HashSet<String> = HashSet::new();
+        for segment in segments.keys() {
+            let signature: Option<&String> = graph_signatures.get(segment);
+            let new_id: String = match signature.and_then(|signature| signatures.get(signature)) {
+                Some((other_graph, id)) if *other_graph != graph_index => {
+                    merged_segments.insert(segment.clone());
+                    id.clone()
+                }
+                _ if graph_index == 0 => segment.clone(),
+                _ => {
+                    while used_ids.contains(&next_id.to_string()) {
+                        next_id += 1;
+                    }
+                    next_id.to_string()
+                }
+            };
+            if let Some(signature) = signature {
+                signatures
+                    .entry(signature.clone())
+                    .or_insert((graph_index, new_id.clone()));
             }
-            print!("{}", line)
+            used_ids.insert(new_id.clone());
+            mapping.insert(segment.clone(), new_id);
         }
-        line.clear(); // Clear the line buffer for the next read
+        if graph_index == 0 {
+            // Next graphs are numbered after the highest numeric ID of the first one
+            next_id = segments
+                .keys()
+                .filter_map(|segment| segment.parse::<u64>().ok())
+                .max()
+                .map_or(1, |max_id| max_id + 1);
+        }
+        mappings.push(mapping);
+        merged.push(merged_segments);
     }
 
-    // next, we iterate on the second file
-    let file_b: File = File::open(graph_to_append)?;
-    let mut reader_b: BufReader<File> = BufReader::new(file_b);
-    let mut node_mappings: HashMap<u32, u32> = HashMap::new();
-    let mut line: String = String::new();
-    let mut e_lines: Vec<String> = Vec::new();
-    let mut new_id: u32 = max_node_id + 1;
-
-    while reader_b.read_line(&mut line)? > 0 {
-        if line.ends_with('\n') || line.ends_with('\r') {
-            line.pop();
+    if let Some(output_mapping) = output_mapping {
+        let output_file: File = File::create(output_mapping)?;
+        let mut file_writer: BufWriter<File> = BufWriter::new(output_file);
+        for (graph, mapping) in graphs.iter().zip(mappings.iter()) {
+            for (old_id, new_id) in mapping.iter() {
+                writeln!(file_writer, "{}\t{}\t{}", graph, old_id, new_id)?;
+            }
         }
-        let columns: Vec<&str> = line.split('\t').collect();
-        if let Some(first_char) = line.chars().next() {
-            if first_char == 'S' {
-                // Rename nodes given a linear contiguous attribution
-                let node_name: u32 = columns[1].parse::<u32>().unwrap();
-                node_mappings.insert(node_name, new_id);
-                println!("{}\t{}\t{}", columns[0], new_id, columns[2..].join("\t"));
-                new_id += 1;
-            } else if first_char == 'L' {
-                // Store line for later
-                e_lines.push(line.clone());
-            } else if first_char == 'P' {
-                // Rename all nodes given the map
-                let mut chain: String = String::new();
-                let node_list: Vec<String> = columns[2]
-                    .trim()
-                    .split(',')
-                    .map(|s| s.to_string())
-                    .collect();
-                for node_desc in node_list {
-                    let (node, orientation) = node_desc.split_at(node_desc.len() - 1);
-                    if let Some(&mapped_id) = node_mappings.get(&node.parse::<u32>().unwrap()) {
-                        chain += &format!("{}{},", mapped_id, orientation);
-                    } else {
-                        eprintln!("Node {} not found in mappings", node);
-                        exit(1);
+        file_writer.flush()?;
+    }
+
+    // W-lines were introduced in GFA1.1
+    if has_walks
+        && header_tags
+            .get("VN")
+            .is_none_or(|version| parse_version(version) < parse_version("VN:Z:1.1"))
+    {
+        header_tags.insert(String::from("VN"), String::from("VN:Z:1.1"));
+    }
+    if !header_tags.is_empty() {
+        let tags: Vec<&str> = header_tags.values().map(String::as_str).collect();
+        writeln!(writer, "H\t{}", tags.join("\t"))?;
+    }
+
+    // Second pass, each line is printed with its segments renamed
+    let mut printed_links: HashSet<Link> = HashSet::new();
+    for (graph_index, graph) in graphs.iter().enumerate() {
+        let file: File = File::open(graph)?;
+        let mut reader: BufReader<File> = BufReader::new(file);
+        let mut line: String = String::new();
+        let mapping: &IndexMap<String, String> = &mappings[graph_index];
+
+        while reader.read_line(&mut line)? > 0 {
+            let mut columns: Vec<String> = line
+                .trim_end_matches(['\n', '\r'])
+                .split('\t')
+                .map(String::from)
+                .collect();
+            match line.chars().next() {
+                Some('H') => {
+                    line.clear();
+                    continue;
+                }
+                Some('S') => {
+                    if merged[graph_index].contains(&columns[1]) {
+                        line.clear();
+                        continue;
+                    }
+                    columns[1] = map_id(mapping, &columns[1])?;
+                }
+                Some('L') | Some('C') | Some('J') => {
+                    columns[1] = map_id(mapping, &columns[1])?;
+                    columns[3] = map_id(mapping, &columns[3])?;
+                    if merge && line.starts_with('L') {
+                        let link: Link = canonical_link(
+                            &columns[1],
+                            columns[2].chars().next().unwrap_or('+'),
+                            &columns[3],
+                            columns[4].chars().next().unwrap_or('+'),
+                        );
+                        if !printed_links.insert(link) {
+                            line.clear();
+                            continue;
+                        }
+                    }
+                }
+                Some('P') => {
+                    let mut steps: Vec<String> = Vec::new();
+                    for (node, orientation) in parse_p_steps(&columns[2])? {
+                        steps.push(format!("{}{}", map_id(mapping, &node)?, orientation));
+                    }
+                    columns[2] = steps.join(",");
+                }
+                Some('W') => {
+                    let mut walk: String = String::new();
+                    for (node, orientation) in parse_w_steps(&columns[6])? {
+                        walk.push(orientation);
+                        walk += &map_id(mapping, &node)?;
                     }
+                    columns[6] = walk;
                 }
-                chain.truncate(chain.len() - 1);
-                println!("{}\t{}\t{}\t{}", columns[0], columns[1], chain, columns[3]);
-            } else if first_char == 'W' {
-                eprintln!("W-lines not implemented yet");
-                exit(1);
+                _ => (),
             }
+            writeln!(writer, "{}", columns.join("\t"))?;
+            line.clear(); // Clear the line buffer for the next read
         }
-        line.clear(); // Clear the line buffer for the next read
-                      // Treat L-lines
     }
-    for edge in e_lines {
-        let cols: Vec<&str> = edge.split('\t').collect();
-        if let (Some(&mapped_id_from), Some(&mapped_id_to)) = (
-            node_mappings.get(&cols[1].parse::<u32>().unwrap()),
-            node_mappings.get(&cols[3].parse::<u32>().unwrap()),
-        ) {
-            println!(
-                "{}\t{}\t{}\t{}\t{}\t{}",
-                cols[0], mapped_id_from, cols[2], mapped_id_to, cols[4], cols[5]
-            )
+
+    Ok(())
+}
+
+fn map_id(mapping: &IndexMap<String, String>, node: &str) -> io::Result<String> {
+    mapping.get(node).cloned().ok_or_else(|| {
+        eprintln!("Node {} not found in mappings", node);
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Segment not found in graph: {}", node),
+        )
+    })
+}
+
+fn merge_header_tag(header_tags: &mut IndexMap<String, String>, tag: &str, graph: &str) {
+    /*
+    Adds a tag of a H-line to the merged header
+    The highest version is kept for VN, other conflicting tags are kept from the first graph
+     */
+    let name: String = tag.split(':').next().unwrap_or_default().to_string();
+    match header_tags.get(&name) {
+        None => {
+            header_tags.insert(name, tag.to_string());
         }
+        Some(previous) if name == "VN" && parse_version(previous) < parse_version(tag) => {
+            header_tags.insert(name, tag.to_string());
+        }
+        Some(previous) if name != "VN" && previous != tag => {
+            eprintln!(
+                "Header tag {} of {} conflicts with {}, keeping the first one",
+                tag, graph, previous
+            );
+        }
+        _ => (),
     }
+}
 
-    Ok(())
+fn parse_version(tag: &str) -> Vec<u64> {
+    /*
+    Numeric components of a VN:Z tag, so that 1.10 is higher than 1.9
+     */
+    tag.rsplit(':')
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|component| component.parse::<u64>().unwrap_or(0))
+        .collect()
+}
+
+fn canonical_link(from: &str, from_orientation: char, to: &str, to_orientation: char) -> Link {
+    /*
+    A link and its reverse (to, !to_orientation, from, !from_orientation) are the same link
+     */
+    let flip = |orientation: char| if orientation == '+' { '-' } else { '+' };
+    let forward = (
+        from.to_string(),
+        from_orientation,
+        to.to_string(),
+        to_orientation,
+    );
+    let reverse = (
+        to.to_string(),
+        flip(to_orientation),
+        from.to_string(),
+        flip(from_orientation),
+    );
+    forward.min(reverse)
+}
+
+fn neighborhood_signatures(
+    segments: &IndexMap<String, String>,
+    links: &[Link],
+) -> HashMap<String, String> {
+    /*
+    Signature of each segment: its sequence, followed by the sequences of its neighbors
+    read away from the segment, for each side ('L' or 'R') of the segment
+     */
+    let oriented = |node: &str, forward: bool| -> String {
+        let sequence: &str = segments.get(node).map_or("", String::as_str);
+        if forward {
+            sequence.to_string()
+        } else {
+            reverse_complement(sequence)
+        }
+    };
+    let mut neighbors: HashMap<&str, Vec<String>> = HashMap::new();
+    for (from, from_orientation, to, to_orientation) in links.iter() {
+        let (from_side, to_forward) = if *from_orientation == '+' {
+            ('R', *to_orientation == '+')
+        } else {
+            ('L', *to_orientation == '-')
+        };
+        neighbors.entry(from.as_str()).or_default().push(format!(
+            "{}{}",
+            from_side,
+            oriented(to, to_forward)
+        ));
+        let (to_side, from_forward) = if *to_orientation == '+' {
+            ('L', *from_orientation == '-')
+        } else {
+            ('R', *from_orientation == '+')
+        };
+        neighbors.entry(to.as_str()).or_default().push(format!(
+            "{}{}",
+            to_side,
+            oriented(from, from_forward)
+        ));
+    }
+    segments
+        .iter()
+        .map(|(segment, sequence)| {
+            let mut segment_neighbors: Vec<String> =
+                neighbors.remove(segment.as_str()).unwrap_or_default();
+            segment_neighbors.sort();
+            (
+                segment.clone(),
+                format!("{}\t{}", sequence, segment_neighbors.join(",")),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    #[test]
+    fn test_parse_version() {
+        assert!(parse_version("VN:Z:1.10") > parse_version("VN:Z:1.9"));
+        assert!(parse_version("VN:Z:1.1") > parse_version("VN:Z:1.0"));
+        assert_eq!(parse_version("VN:Z:2"), vec![2]);
+    }
+
+    #[test]
+    fn test_write_graphs() {
+        let directory: std::path::PathBuf = temp_dir("test_write_graphs");
+        let graph = |name: &str, content: &str| -> String {
+            let file_path: String = directory.join(name).to_string_lossy().to_string();
+            std::fs::write(&file_path, content).unwrap();
+            file_path
+        };
+        let first: String = graph(
+            "first.gfa",
+            "H\tVN:Z:1.9\nS\t1\tACGT\nS\t2\tA\nL\t1\t+\t2\t+\t0M\nP\tHG1#1#chr1\t1+,2+\t*\n",
+        );
+        let second: String = graph(
+            "second.gfa",
+            "H\tVN:Z:1.10\nS\t1\tGG\nW\tHG2\t1\tchr1\t0\t2\t>1\n",
+        );
+        let duplicate: String = graph("duplicate.gfa", "S\t1\tGG\nP\tHG1#1#chr1\t1+\t*\n");
+        let mut output: Vec<u8> = Vec::new();
+        let result: io::Result<()> = write_graphs(&first, &[second], None, false, &mut output);
+        let mut duplicate_output: Vec<u8> = Vec::new();
+        let duplicate_result: io::Result<()> =
+            write_graphs(&first, &[duplicate], None, false, &mut duplicate_output);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(result.is_ok());
+        // 1.10 is a later version than 1.9, and segments of the second graph are renumbered
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "H\tVN:Z:1.10\nS\t1\tACGT\nS\t2\tA\nL\t1\t+\t2\t+\t0M\nP\tHG1#1#chr1\t1+,2+\t*\n\
+             S\t3\tGG\nW\tHG2\t1\tchr1\t0\t2\t>3\n"
+        );
+        // A path name seen in two graphs is an error, before anything is printed
+        assert!(duplicate_result.is_err());
+        assert!(duplicate_output.is_empty());
+    }
 }
@@ -1,27 +1,33 @@
-use std::collections::HashMap;
+use crate::walks::{parse_p_steps, parse_w_steps, read_lengths, split_pansn, PanSN};
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Seek};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, Write};
 
 struct Tree {
     // A tree is a collection of nodes
-    nodes: HashMap<i32, Node>,
+    nodes: HashMap<String, Node>,
 }
 
 #[derive(Clone)]
 struct Path {
     // A path is a string name
     name: String,
-    index: i32,
+}
+
+struct StableSequence {
+    // A stable sequence of a rGFA, with its segments as (offset, length, identifier)
+    rank: u32,
+    segments: Vec<(u64, u64, String)>,
 }
 
 #[derive(Clone)]
 struct Node {
-    // A node is an identifier, a path, and a list of children (as identifiers)
-    id: i32,
+    // A node is a path, an offset on this path, a rank, and a list of children (as identifiers)
     path: Path,
-    length: i32,
-    offset: i32,
-    children: Vec<i32>,
+    offset: u64,
+    rank: u32,
+    children: Vec<String>,
 }
 
 impl Tree {
@@ -31,30 +37,32 @@ impl Tree {
         }
     }
 
-    fn add_node(&mut self, id: i32, path: Path, length: i32, offset: i32) {
-        self.nodes.insert(id, Node::new(id, path, length, offset));
+    fn add_node(&mut self, id: &str, path: Path, offset: u64) {
+        self.nodes.insert(id.to_string(), Node::new(path, offset));
+    }
+
+    fn add_child(&mut self, parent_id: &str, child_id: &str) {
+        let children: &mut Vec<String> = &mut self.nodes.get_mut(parent_id).unwrap().children;
+        if !children.iter().any(|child| child == child_id) {
+            children.push(child_id.to_string());
+        }
     }
 
-    fn add_child(&mut self, parent_id: i32, child_id: i32) {
-        self.nodes
-            .get_mut(&parent_id)
-            .unwrap()
-            .children
-            .push(child_id);
+    fn get_node(&self, id: &str) -> Option<&Node> {
+        self.nodes.get(id)
     }
 
-    fn get_node(&self, id: i32) -> &Node {
-        self.nodes.get(&id).unwrap()
+    fn set_rank(&mut self, id: &str, rank: u32) {
+        self.nodes.get_mut(id).unwrap().rank = rank;
     }
 }
 
 impl Node {
-    fn new(id: i32, path: Path, length: i32, offset: i32) -> Node {
+    fn new(path: Path, offset: u64) -> Node {
         Node {
-            id: id,
-            path: path,
-            length: length,
-            offset: offset,
+            path,
+            offset,
+            rank: 0,
             children: Vec::new(),
         }
     }
@@ -63,29 +71,26 @@ impl Node {
         &self.path
     }
 
-    fn get_offset(&self) -> i32 {
+    fn get_offset(&self) -> u64 {
         self.offset
     }
+
+    fn get_rank(&self) -> u32 {
+        self.rank
+    }
 }
 
 impl Path {
-    fn new(name: String, index: i32) -> Path {
-        Path {
-            name: name,
-            index: index,
-        }
+    fn new(name: String) -> Path {
+        Path { name }
     }
 
     fn get_name(&self) -> &String {
         &self.name
     }
-
-    fn get_index(&self) -> i32 {
-        self.index
-    }
 }
 
-pub fn gfa_to_rgfa(file_path: &str, reference: &str) -> io::Result<()> {
+pub fn gfa_to_rgfa(file_path: &str, reference: &str, order: &[String]) -> io::Result<()> {
     /*
     This function reads a GFA file and prints the rGFA version of the file
     rGFA is a subset of GFA, with only the S and L lines
@@ -94,18 +99,36 @@ pub fn gfa_to_rgfa(file_path: &str, reference: &str) -> io::Result<()> {
     * SO (integer): Offset on the stable sequence
     * SR (integer): Rank: 0 if on a linear reference genome, >0 otherwise.
     Due to the nature of the GFA format, all paths should be stored.
+    Paths can be P-lines or W-lines, the later being named sample#haplotype#seqid.
+
+    Each segment is assigned to the first path (starting with the reference) that crosses it,
+    and its offset is the position of its first visit in this path.
+    Cyclic paths are thus supported, but later visits of a segment are not represented.
+    Paths are processed in the given order, then in the order of the file.
+    Ranks follow the depth in the tree: a run of segments that branches off segments of rank k-1
+    has rank k. Segments crossed by no path are their own stable sequence, with a rank above all paths.
+     */
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    write_rgfa(file_path, reference, order, &mut writer)?;
+    writer.flush()
+}
 
-    Will only work on acyclic graphs.
-    Problem is that the only tool that break cycles is odgi break.
-    And odgi break drops paths when breaking cycles.
-    If we use this on cyclic graphs, we will have inconsistent offsets (SO field).
+fn write_rgfa<W: Write>(
+    file_path: &str,
+    reference: &str,
+    order: &[String],
+    writer: &mut W,
+) -> io::Result<()> {
+    /*
+    Writes the rGFA version of the file to the given writer (see gfa_to_rgfa)
+    Segments without sequence ('*') take their length from their LN tag
      */
+    let sequence_lengths: HashMap<String, u64> = read_lengths(file_path)?;
     let file: File = File::open(file_path)?;
     let mut reader: BufReader<File> = BufReader::new(file);
-    let mut paths: HashMap<String, Vec<String>> = HashMap::new();
+    let mut paths: IndexMap<String, Vec<String>> = IndexMap::new();
     let mut line: String = String::new();
-    let mut sequence_lengths: HashMap<i32, i32> = HashMap::new();
-    let mut path_index: i32 = 0;
 
     // We represent as a tree the paths that are given in the file
     // it is mandatory to derive the SO, SR, and SN fields
@@ -113,105 +136,449 @@ pub fn gfa_to_rgfa(file_path: &str, reference: &str) -> io::Result<()> {
 
     // We need to read the file a first time to store node lists
     while reader.read_line(&mut line)? > 0 {
-        let columns: Vec<&str> = line.split('\t').collect();
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
         if let Some(first_char) = line.chars().next() {
-            if first_char == 'S' {
-                let seq_name: i32 = columns[1].parse().unwrap();
-                let seq_length: i32 = columns[2].len() as i32;
-                sequence_lengths.insert(seq_name, seq_length);
-            }
             if first_char == 'P' {
                 let path_name: String = String::from(columns[1]);
-                let node_list: Vec<String> = columns[2]
-                    .trim()
-                    .split(',')
-                    .map(|s| s[..s.len() - 1].to_string())
+                let node_list: Vec<String> = parse_p_steps(columns[2])?
+                    .into_iter()
+                    .map(|(node, _)| node)
+                    .collect();
+                paths.insert(path_name, node_list);
+            }
+            if first_char == 'W' {
+                let path_name: String = format!("{}#{}#{}", columns[1], columns[2], columns[3]);
+                let node_list: Vec<String> = parse_w_steps(columns[6])?
+                    .into_iter()
+                    .map(|(node, _)| node)
                     .collect();
                 paths.insert(path_name, node_list);
             }
         }
         line.clear(); // Clear the line buffer for the next read
     }
-    // We fill the tree, reading path per path the graph
-    let refpath: Path = Path::new(reference.to_string(), path_index);
-    let mut offset: i32 = 0;
 
     // We start by the reference path
-    let mut parent_node: Option<Node> = None;
-    for node_name in paths.get(reference).unwrap() {
-        let node_id: i32 = node_name.parse().unwrap();
-        let node_length: i32 = sequence_lengths.get(&node_id).unwrap().clone();
+    let reference_nodes: Vec<String> = paths.shift_remove(reference).ok_or_else(|| {
+        eprintln!("Reference {} not found in graph", reference);
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Path not found in graph: {}", reference),
+        )
+    })?;
+    let refpath: Path = Path::new(reference.to_string());
+    add_path_to_tree(
+        &mut tree,
+        &refpath,
+        &reference_nodes,
+        &sequence_lengths,
+        true,
+    )?;
+
+    // Paths given by the user come first, in the given order
+    for (index, path_name) in order.iter().enumerate() {
+        match paths.get_index_of(path_name) {
+            Some(position) => paths.move_index(position, index),
+            None => eprintln!(
+                "Path {} not found in graph, ignored for ordering",
+                path_name
+            ),
+        }
+    }
+    // We add all the other paths to the tree
+    for (path_name, node_list) in paths.iter() {
+        let path: Path = Path::new(path_name.to_string());
+        add_path_to_tree(&mut tree, &path, node_list, &sequence_lengths, false)?;
+    }
+    // Segments that are not in any path get a rank above all paths
+    let unplaced_rank: u32 = tree
+        .nodes
+        .values()
+        .map(|node| node.get_rank())
+        .max()
+        .unwrap_or(0)
+        + 1;
+
+    // We read the file a second time to print the rGFA version
+    reader.seek(io::SeekFrom::Start(0))?;
+    while reader.read_line(&mut line)? > 0 {
+        let columns: Vec<&str> = line.split('\t').collect();
+        if let Some(first_char) = line.chars().next() {
+            if first_char == 'S' {
+                let (sn, so, sr) = match tree.get_node(columns[1]) {
+                    Some(node) => (
+                        node.get_path().get_name().clone(),
+                        node.get_offset(),
+                        node.get_rank(),
+                    ),
+                    // The segment is its own stable sequence
+                    None => (columns[1].to_string(), 0, unplaced_rank),
+                };
+                // In the case of an S-line, we add the SN, SO, and SR fields
+                writeln!(
+                    writer,
+                    "{}\tSN:Z:{}\tSO:i:{}\tSR:i:{}",
+                    line.trim_end(),
+                    sn,
+                    so,
+                    sr
+                )?;
+            }
+            if first_char == 'H' || first_char == 'L' || first_char == 'E' {
+                // In the case of a header or an edge, we print without modification
+                writeln!(writer, "{}", line.trim_end())?;
+            }
+            // In any other case, we don't print the line
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    Ok(())
+}
+
+fn add_path_to_tree(
+    tree: &mut Tree,
+    path: &Path,
+    node_list: &[String],
+    sequence_lengths: &HashMap<String, u64>,
+    reference: bool,
+) -> io::Result<()> {
+    /*
+    Walks along a path, adding to the tree the nodes that are not already in it
+    The offset of a new node is its position in the path, and its parent is the previous node
+    New nodes come in runs, each run branching off the nodes already in the tree that surround it:
+    the rank of a run is one more than the lowest rank of these nodes (0 for the reference)
+     */
+    let mut offset: u64 = 0;
+    let mut parent_node: Option<&str> = None;
+    let mut visited: HashSet<&str> = HashSet::new();
+    let mut revisits: usize = 0;
+    // Run of new nodes currently being added, and rank of the node preceding it
+    let mut run: Vec<&str> = Vec::new();
+    let mut run_nodes: HashSet<&str> = HashSet::new();
+    let mut left_rank: Option<u32> = None;
+    for node_id in node_list {
+        let node_length: u64 = *sequence_lengths.get(node_id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Segment not found in graph: {}", node_id),
+            )
+        })?;
         // We add the node if it is not already in the tree
-        if !tree.nodes.contains_key(&node_id) {
-            tree.add_node(node_id, refpath.clone(), node_length, offset);
+        if !tree.nodes.contains_key(node_id) {
+            tree.add_node(node_id, path.clone(), offset);
+            run.push(node_id);
+            run_nodes.insert(node_id);
+        } else if !run_nodes.contains(node_id.as_str()) {
+            // The node was already in the tree: it closes the current run
+            let right_rank: u32 = tree.get_node(node_id).unwrap().get_rank();
+            close_run(tree, &mut run, left_rank, Some(right_rank), reference);
+            run_nodes.clear();
+            left_rank = Some(right_rank);
         }
         // For all nodes except the first one, set the previous node as a parent
-        if let Some(parent) = parent_node.clone() {
-            tree.add_child(parent.id, node_id);
+        if let Some(parent) = parent_node {
+            tree.add_child(parent, node_id);
         }
-        parent_node = Some(tree.nodes.get(&node_id).unwrap().clone());
+        if !visited.insert(node_id) {
+            revisits += 1;
+        }
+        parent_node = Some(node_id);
         offset += node_length;
     }
+    close_run(tree, &mut run, left_rank, None, reference);
+    if revisits > 0 {
+        eprintln!(
+            "Path {} is cyclic ({} segment visits are repeats), offsets are given for the first visit",
+            path.get_name(),
+            revisits
+        );
+    }
+    Ok(())
+}
 
-    // Remove the reference path from the paths
-    paths.remove(reference);
-
-    // We add all the other paths to the tree
-    for (path_name, node_list) in paths.iter() {
-        path_index += 1;
-        let path: Path = Path::new(path_name.to_string(), path_index);
-        parent_node = None;
-        // If the node is not in the tree, we add it only if there is a parent
-        // If the node is in the tree, and it exists a parent, we add the node as a child
-        for node_name in node_list {
-            let node_id: i32 = node_name.parse().unwrap();
-            let node_length: i32 = sequence_lengths.get(&node_id).unwrap().clone();
-            // We add the node if it is not already in the tree
-            if !tree.nodes.contains_key(&node_id) && parent_node.is_some() {
-                tree.add_node(
-                    node_id,
-                    path.clone(),
-                    node_length,
-                    parent_node.as_ref().unwrap().offset + parent_node.as_ref().unwrap().length,
-                );
-            }
-            // For all nodes except the first one, set the previous node as a parent
-            if let Some(parent) = parent_node.clone() {
-                tree.add_child(parent.id, node_id);
-            }
-            // If the node is in the tree, it becomes the new parent
-            if tree.nodes.contains_key(&node_id) {
-                parent_node = Some(tree.nodes.get(&node_id).unwrap().clone());
-            }
+fn close_run(
+    tree: &mut Tree,
+    run: &mut Vec<&str>,
+    left_rank: Option<u32>,
+    right_rank: Option<u32>,
+    reference: bool,
+) {
+    /*
+    Sets the rank of a run of new nodes, given the ranks of the nodes that surround it
+    A run that is attached to no node of the tree branches off nothing, and gets rank 1
+     */
+    let rank: u32 = if reference {
+        0
+    } else {
+        match (left_rank, right_rank) {
+            (Some(left), Some(right)) => left.min(right) + 1,
+            (Some(rank), None) | (None, Some(rank)) => rank + 1,
+            (None, None) => 1,
         }
+    };
+    for node_id in run.drain(..) {
+        tree.set_rank(node_id, rank);
     }
+}
 
-    // We read the file a second time to print the rGFA version
-    reader.seek(io::SeekFrom::Start(0))?;
+pub fn rgfa_to_gfa(file_path: &str, walks: bool) -> io::Result<()> {
+    /*
+    This function reads a rGFA file and prints the GFA version of the file, with paths
+    Segments are grouped by stable sequence (SN tag) and sorted by offset (SO tag)
+    Each run of contiguous segments, where each segment starts where the previous one ends and
+    both are linked, becomes a P-line (or a W-line).
+    Runs that do not cover a whole stable sequence are named name:start-end
+    Segments without rGFA tags are kept, but are not part of any path
+    W-lines were introduced in GFA1.1: when they are written, the header is set to VN:Z:1.1
+     */
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    write_gfa(file_path, walks, &mut writer)?;
+    writer.flush()
+}
+
+fn write_gfa<W: Write>(file_path: &str, walks: bool, writer: &mut W) -> io::Result<()> {
+    /*
+    Writes the GFA version of the rGFA file to the given writer (see rgfa_to_gfa)
+     */
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line: String = String::new();
+    let mut stable_sequences: IndexMap<String, StableSequence> = IndexMap::new();
+    let mut links: HashSet<(String, char, String, char)> = HashSet::new();
+
+    if walks {
+        writeln!(writer, "H\tVN:Z:1.1")?;
+    }
     while reader.read_line(&mut line)? > 0 {
-        let columns: Vec<&str> = line.split('\t').collect();
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
         if let Some(first_char) = line.chars().next() {
             if first_char == 'S' {
-                let node_id: i32 = columns[1].parse().unwrap();
-                if tree.nodes.contains_key(&node_id) {
-                    let sn: String = tree.get_node(node_id).get_path().get_name().clone();
-                    let so: i32 = tree.get_node(node_id).get_offset();
-                    let sr: i32 = tree.get_node(node_id).get_path().get_index();
-                    // In the case of an S-line, we add the SN, SO, and SR fields
-                    println!("{}\tSN:Z:{}\tSO:i:{}\tSR:i:{}", line.trim_end(), sn, so, sr);
+                let length: u64 = seq_lengths[columns[1]];
+                let (mut sn, mut so, mut sr) = (None, None, 0);
+                for tag in columns[3..].iter() {
+                    if let Some(value) = tag.strip_prefix("SN:Z:") {
+                        sn = Some(value.to_string());
+                    } else if let Some(Ok(value)) = tag.strip_prefix("SO:i:").map(str::parse) {
+                        so = Some(value);
+                    } else if let Some(Ok(value)) = tag.strip_prefix("SR:i:").map(str::parse) {
+                        sr = value;
+                    }
+                }
+                if let (Some(sn), Some(so)) = (sn, so) {
+                    stable_sequences
+                        .entry(sn)
+                        .or_insert(StableSequence {
+                            rank: sr,
+                            segments: Vec::new(),
+                        })
+                        .segments
+                        .push((so, length, columns[1].to_string()));
                 } else {
-                    // In the case of an S-line, we print without modification
-                    println!("{}", line.trim_end());
+                    eprintln!("Segment {} has no SN and SO tags", columns[1]);
                 }
             }
-            if first_char == 'E' {
-                // In the case of a E-line, we print without modification
-                println!("{}", line.trim_end());
+            if first_char == 'L' {
+                links.insert((
+                    columns[1].to_string(),
+                    columns[2].chars().next().unwrap_or('+'),
+                    columns[3].to_string(),
+                    columns[4].chars().next().unwrap_or('+'),
+                ));
+            }
+            if first_char == 'H' && walks {
+                // Other header tags are kept, on their own line
+                let tags: Vec<&str> = columns[1..]
+                    .iter()
+                    .filter(|tag| !tag.starts_with("VN:"))
+                    .copied()
+                    .collect();
+                if !tags.is_empty() {
+                    writeln!(writer, "H\t{}", tags.join("\t"))?;
+                }
+            } else if first_char != 'P' && first_char != 'W' {
+                write!(writer, "{}", line)?;
             }
-            // In any other case, we don't print the line
         }
         line.clear(); // Clear the line buffer for the next read
     }
 
+    // Stable sequences are written by rank, then in order of appearance
+    stable_sequences.sort_by(|_, a, _, b| a.rank.cmp(&b.rank));
+    for (name, StableSequence { segments, .. }) in stable_sequences.iter_mut() {
+        segments.sort();
+        let sequence_end: u64 = segments.last().map(|(so, len, _)| so + len).unwrap_or(0);
+        let mut runs: Vec<Vec<&(u64, u64, String)>> = Vec::new();
+        for segment in segments.iter() {
+            match runs
+                .last_mut()
+                .and_then(|run| run.last().copied().map(|last| (run, last)))
+            {
+                Some((run, (so, len, id)))
+                    if so + len == segment.0
+                        && (links.contains(&(id.clone(), '+', segment.2.clone(), '+'))
+                            || links.contains(&(segment.2.clone(), '-', id.clone(), '-'))) =>
+                {
+                    run.push(segment)
+                }
+                Some((_, (so, len, id))) => {
+                    eprintln!(
+                        "Stable sequence {} is broken between {} (ends at {}) and {} (starts at {})",
+                        name,
+                        id,
+                        so + len,
+                        segment.2,
+                        segment.0
+                    );
+                    runs.push(vec![segment]);
+                }
+                None => runs.push(vec![segment]),
+            }
+        }
+        for run in runs {
+            let start: u64 = run[0].0;
+            let end: u64 = run.last().map(|(so, len, _)| so + len).unwrap_or(start);
+            if walks {
+                let pansn: Option<PanSN> = split_pansn(name);
+                let (sample, haplotype, seqid) = match &pansn {
+                    Some(pansn) => (pansn.sample, pansn.haplotype, pansn.contig),
+                    None => (name.as_str(), "0", name.as_str()),
+                };
+                let walk: String = run.iter().map(|(_, _, id)| format!(">{}", id)).collect();
+                writeln!(
+                    writer,
+                    "W\t{}\t{}\t{}\t{}\t{}\t{}",
+                    sample, haplotype, seqid, start, end, walk
+                )?;
+            } else {
+                let path_name: String = if start == 0 && end == sequence_end {
+                    name.clone()
+                } else {
+                    format!("{}:{}-{}", name, start, end)
+                };
+                let path: Vec<String> = run.iter().map(|(_, _, id)| format!("{}+", id)).collect();
+                writeln!(writer, "P\t{}\t{}\t*", path_name, path.join(","))?;
+            }
+        }
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_file;
+
+    #[test]
+    fn test_ranks() {
+        let sequence_lengths: HashMap<String, u64> =
+            (1..=6).map(|node| (node.to_string(), 1)).collect();
+        let as_nodes =
+            |nodes: &[&str]| -> Vec<String> { nodes.iter().map(|node| node.to_string()).collect() };
+        let mut tree: Tree = Tree::new();
+        let reference: Path = Path::new(String::from("ref"));
+        let first: Path = Path::new(String::from("first"));
+        let second: Path = Path::new(String::from("second"));
+        add_path_to_tree(
+            &mut tree,
+            &reference,
+            &as_nodes(&["1", "2", "3"]),
+            &sequence_lengths,
+            true,
+        )
+        .unwrap();
+        // 4 branches off the reference, 5 branches off 4
+        add_path_to_tree(
+            &mut tree,
+            &first,
+            &as_nodes(&["1", "4", "3"]),
+            &sequence_lengths,
+            false,
+        )
+        .unwrap();
+        add_path_to_tree(
+            &mut tree,
+            &second,
+            &as_nodes(&["6", "1", "4", "5"]),
+            &sequence_lengths,
+            false,
+        )
+        .unwrap();
+        assert_eq!(tree.get_node("2").unwrap().get_rank(), 0);
+        assert_eq!(tree.get_node("4").unwrap().get_rank(), 1);
+        assert_eq!(tree.get_node("4").unwrap().get_offset(), 1);
+        assert_eq!(tree.get_node("5").unwrap().get_rank(), 2);
+        assert_eq!(tree.get_node("6").unwrap().get_rank(), 1);
+        assert_eq!(tree.get_node("5").unwrap().get_path().get_name(), "second");
+    }
+
+    #[test]
+    fn test_write_rgfa() {
+        let file_path: String = temp_file("test_write_rgfa.gfa");
+        // The reference is cyclic, and segment 4 is crossed by no path
+        std::fs::write(
+            &file_path,
+            "H\tVN:Z:1.0\nS\t1\tACGT\nS\t2\t*\tLN:i:3\nS\t3\tGG\nS\t4\tT\n\
+             L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nL\t3\t+\t1\t+\t0M\nL\t1\t+\t4\t-\t0M\n\
+             P\tref\t1+,2+,3+,1+,2+\t*\n",
+        )
+        .unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        write_rgfa(&file_path, "ref", &[], &mut output).unwrap();
+        let unknown_reference: io::Result<()> =
+            write_rgfa(&file_path, "chr2", &[], &mut Vec::new());
+        std::fs::remove_file(&file_path).unwrap();
+
+        // Offsets are given for the first visit, and links are kept as they are
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "H\tVN:Z:1.0\n\
+             S\t1\tACGT\tSN:Z:ref\tSO:i:0\tSR:i:0\n\
+             S\t2\t*\tLN:i:3\tSN:Z:ref\tSO:i:4\tSR:i:0\n\
+             S\t3\tGG\tSN:Z:ref\tSO:i:7\tSR:i:0\n\
+             S\t4\tT\tSN:Z:4\tSO:i:0\tSR:i:1\n\
+             L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nL\t3\t+\t1\t+\t0M\nL\t1\t+\t4\t-\t0M\n"
+        );
+        assert!(unknown_reference.is_err());
+    }
+
+    #[test]
+    fn test_write_gfa() {
+        let file_path: String = temp_file("test_write_gfa.gfa");
+        // Segments 2 and 3 are linked on the reverse strand, 5 has no rGFA tags
+        let segments: &str = "S\t1\tACGT\tSN:Z:chr1\tSO:i:0\tSR:i:0\n\
+             S\t2\t*\tLN:i:3\tSN:Z:chr1\tSO:i:4\tSR:i:0\n\
+             S\t3\tGG\tSN:Z:chr1\tSO:i:7\tSR:i:0\n\
+             S\t4\tT\tSN:Z:HG2#1#chr1\tSO:i:10\tSR:i:1\n\
+             S\t5\tA\n\
+             L\t1\t+\t2\t+\t0M\nL\t3\t-\t2\t-\t0M\nL\t3\t+\t4\t+\t0M\n";
+        std::fs::write(&file_path, format!("H\tVN:Z:1.0\n{}", segments)).unwrap();
+        let convert = |walks: bool| -> String {
+            let mut output: Vec<u8> = Vec::new();
+            write_gfa(&file_path, walks, &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let paths: String = convert(false);
+        let walks: String = convert(true);
+        std::fs::remove_file(&file_path).unwrap();
+
+        // Paths that do not start at the beginning of their stable sequence are named after their range
+        assert_eq!(
+            paths,
+            format!(
+                "H\tVN:Z:1.0\n{}P\tchr1\t1+,2+,3+\t*\nP\tHG2#1#chr1:10-11\t4+\t*\n",
+                segments
+            )
+        );
+        assert_eq!(
+            walks,
+            format!(
+                "H\tVN:Z:1.1\n{}W\tchr1\t0\tchr1\t0\t9\t>1>2>3\nW\tHG2\t1\tchr1\t10\t11\t>4\n",
+                segments
+            )
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use crate::index_gfa_file::{path_offsets, read_path_offsets, StepOffset};
+use crate::walks::read_lengths;
+use indexmap::IndexMap;
+use petgraph::dot::{Config, Dot};
+use petgraph::graph::{Graph, NodeIndex};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+// Colors of the links, by orientation of their ends
+const LINK_COLORS: [(&str, &str); 4] = [
+    ("++", "black"),
+    ("--", "gray50"),
+    ("+-", "red"),
+    ("-+", "blue"),
+];
+// Colors of the paths, reused when there are more paths than colors
+const PATH_COLORS: [&str; 8] = [
+    "orange",
+    "forestgreen",
+    "purple",
+    "deepskyblue",
+    "gold",
+    "deeppink",
+    "sienna",
+    "turquoise",
+];
+
+enum Edge {
+    // Orientations of the ends of a link, as in the L-line
+    Link(char, char),
+    // Step of a path, with the index of the path
+    Path(usize),
+}
+
+impl fmt::Display for Edge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Edge::Link(from, to) => write!(f, "{}{}", from, to),
+            Edge::Path(index) => write!(f, "path {}", index),
+        }
+    }
+}
+
+pub fn export_dot(
+    file_path: &str,
+    nodes: Option<&str>,
+    interval: Option<&str>,
+    context: usize,
+    selected_paths: &[String],
+) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    write_dot(
+        file_path,
+        nodes,
+        interval,
+        context,
+        selected_paths,
+        &mut writer,
+    )?;
+    writer.flush()
+}
+
+fn write_dot<W: Write>(
+    file_path: &str,
+    nodes: Option<&str>,
+    interval: Option<&str>,
+    context: usize,
+    selected_paths: &[String],
+    writer: &mut W,
+) -> io::Result<()> {
+    /*
+    This function reads a GFA file and prints the graph, or a region of it, in the DOT format of Graphviz
+    - nodes: a range of numeric segment IDs (first-last, inclusive) or a single segment ID
+    - interval: a path interval (path:start-end), selecting the segments of the path overlapping it,
+      with the positions given by Offsets
+    - context: number of links followed around the selected segments
+    - selected_paths: paths drawn over the graph (all if empty)
+    Segments are labeled with their ID and length, links are colored by the orientation of their ends
+    (++ black, -- gray, +- red, -+ blue) and each path is drawn as edges of its own color between its steps
+     */
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
+    let mut segments: IndexMap<String, u64> = IndexMap::new();
+    let mut links: Vec<(String, char, String, char)> = Vec::new();
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line: String = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        match line.chars().next() {
+            Some('S') => {
+                segments.insert(columns[1].to_string(), seq_lengths[columns[1]]);
+            }
+            Some('L') => links.push((
+                columns[1].to_string(),
+                columns[2].chars().next().unwrap_or('+'),
+                columns[3].to_string(),
+                columns[4].chars().next().unwrap_or('+'),
+            )),
+            _ => (),
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    // Selected segments, the whole graph if no region is given
+    let mut selected: HashSet<&str> = HashSet::new();
+    if let Some(nodes) = nodes {
+        let range: Option<(u64, u64)> = nodes.split_once('-').and_then(|(first, last)| {
+            Some((first.parse::<u64>().ok()?, last.parse::<u64>().ok()?))
+        });
+        for segment in segments.keys() {
+            let in_range: bool = match (range, segment.parse::<u64>()) {
+                (Some((first, last)), Ok(id)) => first <= id && id <= last,
+                _ => segment == nodes,
+            };
+            if in_range {
+                selected.insert(segment);
+            }
+        }
+        if selected.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Segment not found in graph: {}", nodes),
+            ));
+        }
+    }
+    if let Some(interval) = interval {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid path interval (expected path:start-end): {}",
+                    interval
+                ),
+            )
+        };
+        let (path_name, range) = interval.rsplit_once(':').ok_or_else(invalid)?;
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let (start, end): (u64, u64) = (
+            start.parse::<u64>().map_err(|_| invalid())?,
+            end.parse::<u64>().map_err(|_| invalid())?,
+        );
+        let steps: Vec<StepOffset> = read_path_offsets(file_path, &seq_lengths, path_name)?;
+        for step in steps
+            .iter()
+            .filter(|step| step.start < end && start < step.end)
+        {
+            if let Some((segment, _)) = segments.get_key_value(&step.node) {
+                selected.insert(segment);
+            }
+        }
+    }
+    let whole_graph: bool = nodes.is_none() && interval.is_none();
+    if !whole_graph && context > 0 {
+        let mut neighbors: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (from, _, to, _) in links.iter() {
+            neighbors.entry(from).or_default().push(to);
+            neighbors.entry(to).or_default().push(from);
+        }
+        let mut queue: VecDeque<(&str, usize)> =
+            selected.iter().map(|segment| (*segment, 0)).collect();
+        while let Some((segment, distance)) = queue.pop_front() {
+            if distance == context {
+                continue;
+            }
+            for neighbor in neighbors.get(segment).into_iter().flatten() {
+                if selected.insert(neighbor) {
+                    queue.push_back((neighbor, distance + 1));
+                }
+            }
+        }
+    }
+    let is_selected = |segment: &str| whole_graph || selected.contains(segment);
+
+    let mut graph: Graph<String, Edge> = Graph::new();
+    let mut indices: HashMap<&str, NodeIndex> = HashMap::new();
+    for segment in segments.keys().filter(|segment| is_selected(segment)) {
+        indices.insert(segment, graph.add_node(segment.clone()));
+    }
+    for (from, from_orientation, to, to_orientation) in links.iter() {
+        if let (Some(from), Some(to)) = (indices.get(from.as_str()), indices.get(to.as_str())) {
+            graph.add_edge(*from, *to, Edge::Link(*from_orientation, *to_orientation));
+        }
+    }
+
+    // Paths are drawn between their consecutive steps in the region
+    let mut path_names: Vec<String> = Vec::new();
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    while reader.read_line(&mut line)? > 0 {
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        if let Some((path_name, steps)) = path_offsets(&columns, &seq_lengths)? {
+            if selected_paths.is_empty() || selected_paths.contains(&path_name) {
+                let path_index: usize = path_names.len();
+                let mut drawn: bool = false;
+                for pair in steps.windows(2) {
+                    if let (Some(from), Some(to)) = (
+                        indices.get(pair[0].node.as_str()),
+                        indices.get(pair[1].node.as_str()),
+                    ) {
+                        graph.add_edge(*from, *to, Edge::Path(path_index));
+                        drawn = true;
+                    }
+                }
+                if drawn {
+                    eprintln!(
+                        "Path {} drawn in {}",
+                        path_name,
+                        PATH_COLORS[path_index % PATH_COLORS.len()]
+                    );
+                    path_names.push(path_name);
+                }
+            }
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    let node_attributes = |_, (_, segment): (NodeIndex, &String)| -> String {
+        format!(
+            "label = \"{}\\n{} bp\" shape = box",
+            segment.replace('"', "\\\""),
+            segments[segment.as_str()]
+        )
+    };
+    let edge_attributes = |_, edge: petgraph::graph::EdgeReference<Edge>| -> String {
+        match edge.weight() {
+            Edge::Link(from, to) => {
+                let orientation: String = format!("{}{}", from, to);
+                let color: &str = LINK_COLORS
+                    .iter()
+                    .find(|(link, _)| *link == orientation)
+                    .map_or("black", |(_, color)| color);
+                format!("color = {}", color)
+            }
+            Edge::Path(index) => format!(
+                "color = {} penwidth = 2 constraint = false tooltip = \"{}\"",
+                PATH_COLORS[index % PATH_COLORS.len()],
+                path_names[*index].replace('"', "\\\"")
+            ),
+        }
+    };
+    writeln!(
+        writer,
+        "{}",
+        Dot::with_attr_getters(
+            &graph,
+            &[Config::NodeNoLabel, Config::EdgeNoLabel],
+            &edge_attributes,
+            &node_attributes,
+        )
+    )?;
+    eprintln!(
+        "Exported {} segments and {} edges",
+        graph.node_count(),
+        graph.edge_count()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_file;
+
+    #[test]
+    fn test_write_dot() {
+        let file_path: String = temp_file("test_write_dot.gfa");
+        std::fs::write(
+            &file_path,
+            "S\t1\tACGT\nS\t2\tGG\nS\t3\tT\nS\t4\tCC\nS\t5\tA\n\
+             L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nL\t3\t+\t4\t-\t0M\nL\t4\t-\t5\t+\t0M\n\
+             P\tref\t1+,2+,3+,4-,5+\t*\n",
+        )
+        .unwrap();
+        let dot = |nodes: Option<&str>, interval: Option<&str>, context: usize| -> String {
+            let mut output: Vec<u8> = Vec::new();
+            write_dot(&file_path, nodes, interval, context, &[], &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let around_node: String = dot(Some("2"), None, 1);
+        let interval: String = dot(None, Some("ref:6-7"), 0);
+        let whole_graph: String = dot(None, None, 0);
+        let unknown_node: io::Result<()> =
+            write_dot(&file_path, Some("9"), None, 0, &[], &mut Vec::new());
+        std::fs::remove_file(&file_path).unwrap();
+
+        // Segment 2 and its neighbors, with their links and the steps of the path between them
+        for segment in ["1\\n4 bp", "2\\n2 bp", "3\\n1 bp"] {
+            assert!(around_node.contains(&format!("label = \"{}\"", segment)));
+        }
+        assert!(!around_node.contains("label = \"4\\n"));
+        assert_eq!(around_node.matches("color = black").count(), 2);
+        assert_eq!(around_node.matches("tooltip = \"ref\"").count(), 2);
+        // Position 6 of the path is on segment 3 only
+        assert!(interval.contains("label = \"3\\n1 bp\""));
+        assert_eq!(interval.matches("label = ").count(), 1);
+        assert_eq!(whole_graph.matches("label = ").count(), 5);
+        assert_eq!(whole_graph.matches("color = red").count(), 1);
+        assert_eq!(whole_graph.matches("color = gray50").count(), 0);
+        assert_eq!(whole_graph.matches("color = blue").count(), 1);
+        assert!(unknown_node.is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use crate::index_gfa_file::{path_offsets, StepOffset};
+use crate::sequence::reverse_complement;
+use crate::table::{ColumnType, OutputFormat, TableWriter, Value};
+use crate::walks::read_lengths;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+struct Match {
+    query: usize,
+    // Oriented segments spelling the match (true if forward)
+    walk: Vec<(usize, bool)>,
+    // Offset of the match in the first oriented segment, and end offset in the last one
+    start: usize,
+    end: usize,
+}
+
+struct PathHit {
+    path: String,
+    start: u64,
+    end: u64,
+    strand: char,
+}
+
+pub fn find_sequences(
+    file_path: &str,
+    queries: &[String],
+    fasta_file: Option<&str>,
+    format: OutputFormat,
+) -> io::Result<()> {
+    let rows: Vec<Vec<Value>> = find_rows(file_path, queries, fasta_file)?;
+    let mut table: TableWriter = TableWriter::new(
+        format,
+        &[
+            ("Query", ColumnType::Text),
+            ("Walk", ColumnType::Text),
+            ("WalkStart", ColumnType::Integer),
+            ("WalkEnd", ColumnType::Integer),
+            ("Path", ColumnType::Text),
+            ("PathStart", ColumnType::Integer),
+            ("PathEnd", ColumnType::Integer),
+            ("Strand", ColumnType::Text),
+        ],
+    )?;
+    for row in rows {
+        table.write_row(row)?;
+    }
+    table.finish()
+}
+
+fn find_rows(
+    file_path: &str,
+    queries: &[String],
+    fasta_file: Option<&str>,
+) -> io::Result<Vec<Vec<Value>>> {
+    /*
+    This function reads a GFA file and finds exact matches of DNA queries spelled along the graph
+    - queries: sequences given on the command line, named after themselves
+    - fasta_file: a FASTA file of named queries
+    Matches are searched on both strands, and may span several segments joined by links (overlaps are ignored)
+    Each match is reported with its walk (oriented segments) and offsets in its first and last segments,
+    and once per path traversing it, with its position on the path (as given by Offsets) and strand
+    A summary of the paths containing each query is printed on stderr
+     */
+    let mut named_queries: Vec<(String, Vec<u8>)> = queries
+        .iter()
+        .map(|query| (query.clone(), query.to_ascii_uppercase().into_bytes()))
+        .collect();
+    if let Some(fasta_file) = fasta_file {
+        named_queries.extend(read_fasta(fasta_file)?);
+    }
+    if named_queries.iter().any(|(_, query)| query.is_empty()) || named_queries.is_empty() {
+        eprintln!("Queries must be given with -q or -Q, and must not be empty");
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No query sequence, or an empty query sequence, was given",
+        ));
+    }
+
+    let mut segments: IndexMap<String, Vec<u8>> = IndexMap::new();
+    let mut links: Vec<(String, bool, String, bool)> = Vec::new();
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line: String = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        match line.chars().next() {
+            Some('S') => {
+                segments.insert(
+                    columns[1].to_string(),
+                    columns[2].to_ascii_uppercase().into_bytes(),
+                );
+            }
+            Some('L') => links.push((
+                columns[1].to_string(),
+                columns[2] == "+",
+                columns[3].to_string(),
+                columns[4] == "+",
+            )),
+            _ => (),
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+    let segment_index = |node: &str| -> io::Result<usize> {
+        segments.get_index_of(node).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Segment not found in graph: {}", node),
+            )
+        })
+    };
+    // Successors of each oriented segment, a link being followed in both directions
+    let mut successors: HashMap<(usize, bool), Vec<(usize, bool)>> = HashMap::new();
+    for (from, from_forward, to, to_forward) in links.iter() {
+        let (from, to) = (segment_index(from)?, segment_index(to)?);
+        successors
+            .entry((from, *from_forward))
+            .or_default()
+            .push((to, *to_forward));
+        successors
+            .entry((to, !to_forward))
+            .or_default()
+            .push((from, !from_forward));
+    }
+    for next in successors.values_mut() {
+        next.sort_unstable();
+        next.dedup();
+    }
+    let reverse_sequences: Vec<Vec<u8>> = segments
+        .values()
+        .map(|sequence| reverse_complement(&String::from_utf8_lossy(sequence)).into_bytes())
+        .collect();
+    let oriented = |(index, forward): (usize, bool)| -> &[u8] {
+        if forward {
+            segments[index].as_slice()
+        } else {
+            reverse_sequences[index].as_slice()
+        }
+    };
+
+    // Matches start in an oriented segment, and are extended through links while the query goes on
+    let mut matches: Vec<Match> = Vec::new();
+    for (query_index, (_, query)) in named_queries.iter().enumerate() {
+        for index in 0..segments.len() {
+            for forward in [true, false] {
+                let sequence: &[u8] = oriented((index, forward));
+                for start in 0..sequence.len() {
+                    let suffix: &[u8] = &sequence[start..];
+                    if suffix.starts_with(query) {
+                        matches.push(Match {
+                            query: query_index,
+                            walk: vec![(index, forward)],
+                            start,
+                            end: start + query.len(),
+                        });
+                    } else if query.starts_with(suffix) {
+                        // Partial walks, with the length of the query they already spell
+                        let mut stack: Vec<(Vec<(usize, bool)>, usize)> =
+                            vec![(vec![(index, forward)], suffix.len())];
+                        while let Some((walk, spelled)) = stack.pop() {
+                            let last: (usize, bool) = *walk.last().unwrap();
+                            for next in successors.get(&last).into_iter().flatten() {
+                                let next_sequence: &[u8] = oriented(*next);
+                                let remaining: &[u8] = &query[spelled..];
+                                let mut next_walk: Vec<(usize, bool)> = walk.clone();
+                                next_walk.push(*next);
+                                if next_sequence.starts_with(remaining) {
+                                    matches.push(Match {
+                                        query: query_index,
+                                        walk: next_walk,
+                                        start,
+                                        end: remaining.len(),
+                                    });
+                                } else if remaining.starts_with(next_sequence) {
+                                    stack.push((next_walk, spelled + next_sequence.len()));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Paths are searched for the walks of the matches, in both directions
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
+    let mut candidates: HashMap<usize, Vec<(usize, bool)>> = HashMap::new();
+    for (match_index, found) in matches.iter().enumerate() {
+        candidates
+            .entry(found.walk[0].0)
+            .or_default()
+            .push((match_index, false));
+        candidates
+            .entry(found.walk[found.walk.len() - 1].0)
+            .or_default()
+            .push((match_index, true));
+    }
+    let mut hits: Vec<Vec<PathHit>> = (0..matches.len()).map(|_| Vec::new()).collect();
+    let mut path_count: usize = 0;
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    while reader.read_line(&mut line)? > 0 {
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        if let Some((path_name, offsets)) = path_offsets(&columns, &seq_lengths)? {
+            path_count += 1;
+            let steps: Vec<(usize, bool)> = offsets
+                .iter()
+                .map(|step| Ok((segment_index(&step.node)?, step.orientation == '+')))
+                .collect::<io::Result<Vec<(usize, bool)>>>()?;
+            for (position, (index, _)) in steps.iter().enumerate() {
+                for (match_index, reverse) in candidates.get(index).into_iter().flatten() {
+                    let found: &Match = &matches[*match_index];
+                    let length: usize = found.walk.len();
+                    if position + length > steps.len() {
+                        continue;
+                    }
+                    let traversed: bool = (0..length).all(|offset| {
+                        let step: (usize, bool) = steps[position + offset];
+                        if *reverse {
+                            let (index, forward) = found.walk[length - 1 - offset];
+                            step == (index, !forward)
+                        } else {
+                            step == found.walk[offset]
+                        }
+                    });
+                    if !traversed {
+                        continue;
+                    }
+                    let (first, last): (&StepOffset, &StepOffset) =
+                        (&offsets[position], &offsets[position + length - 1]);
+                    let hit: PathHit = if *reverse {
+                        // The path reads the match on the other strand, from its last segment
+                        let first_length: u64 = first.end - first.start;
+                        let last_length: u64 = last.end - last.start;
+                        PathHit {
+                            path: path_name.clone(),
+                            start: first.start + first_length - found.end as u64,
+                            end: last.start + last_length - found.start as u64,
+                            strand: '-',
+                        }
+                    } else {
+                        PathHit {
+                            path: path_name.clone(),
+                            start: first.start + found.start as u64,
+                            end: last.start + found.end as u64,
+                            strand: '+',
+                        }
+                    };
+                    hits[*match_index].push(hit);
+                }
+            }
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    let mut rows: Vec<Vec<Value>> = Vec::new();
+    for (found, path_hits) in matches.iter().zip(hits.iter()) {
+        let walk: String = found
+            .walk
+            .iter()
+            .map(|(index, forward)| {
+                format!(
+                    "{}{}",
+                    if *forward { '>' } else { '<' },
+                    segments.get_index(*index).unwrap().0
+                )
+            })
+            .collect();
+        let row = |hit: Option<&PathHit>| -> Vec<Value> {
+            vec![
+                Value::Text(named_queries[found.query].0.clone()),
+                Value::Text(walk.clone()),
+                Value::Integer(found.start as i64),
+                Value::Integer(found.end as i64),
+                hit.map_or(Value::Missing, |hit| Value::Text(hit.path.clone())),
+                hit.map_or(Value::Missing, |hit| Value::Integer(hit.start as i64)),
+                hit.map_or(Value::Missing, |hit| Value::Integer(hit.end as i64)),
+                hit.map_or(Value::Missing, |hit| Value::Text(hit.strand.to_string())),
+            ]
+        };
+        if path_hits.is_empty() {
+            rows.push(row(None));
+        }
+        for hit in path_hits.iter() {
+            rows.push(row(Some(hit)));
+        }
+    }
+
+    for (query_index, (name, _)) in named_queries.iter().enumerate() {
+        let query_matches: Vec<usize> = (0..matches.len())
+            .filter(|match_index| matches[*match_index].query == query_index)
+            .collect();
+        let mut paths: Vec<&str> = query_matches
+            .iter()
+            .flat_map(|match_index| hits[*match_index].iter().map(|hit| hit.path.as_str()))
+            .collect();
+        paths.sort_unstable();
+        paths.dedup();
+        eprintln!(
+            "Query {}: {} matches in the graph, found in {} of {} paths",
+            name,
+            query_matches.len(),
+            paths.len(),
+            path_count
+        );
+    }
+
+    Ok(rows)
+}
+
+fn read_fasta(fasta_file: &str) -> io::Result<Vec<(String, Vec<u8>)>> {
+    /*
+    Reads the records of a FASTA file, named after the first word of their header
+     */
+    let file: File = File::open(fasta_file)?;
+    let reader: BufReader<File> = BufReader::new(file);
+    let mut records: Vec<(String, Vec<u8>)> = Vec::new();
+    for line in reader.lines() {
+        let line: String = line?;
+        if let Some(header) = line.strip_prefix('>') {
+            let name: &str = header.split_whitespace().next().unwrap_or_default();
+            records.push((name.to_string(), Vec::new()));
+        } else if let Some((_, sequence)) = records.last_mut() {
+            sequence.extend(line.trim().to_ascii_uppercase().bytes());
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    fn hit(
+        query: &str,
+        walk: &str,
+        walk_offsets: (i64, i64),
+        path_hit: (&str, i64, i64, &str),
+    ) -> Vec<Value> {
+        vec![
+            Value::Text(query.to_string()),
+            Value::Text(walk.to_string()),
+            Value::Integer(walk_offsets.0),
+            Value::Integer(walk_offsets.1),
+            Value::Text(path_hit.0.to_string()),
+            Value::Integer(path_hit.1),
+            Value::Integer(path_hit.2),
+            Value::Text(path_hit.3.to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_find_rows() {
+        let directory: std::path::PathBuf = temp_dir("test_find_rows");
+        let file_path: String = directory.join("graph.gfa").to_string_lossy().to_string();
+        let fasta_file: String = directory.join("queries.fa").to_string_lossy().to_string();
+        // fwd spells ACGT GGA TTC, rev spells its reverse complement GAA TCC ACGT
+        std::fs::write(
+            &file_path,
+            "S\t1\tACGT\nS\t2\tGGA\nS\t3\tTTC\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\n\
+             P\tfwd\t1+,2+,3+\t*\nP\trev\t3-,2-,1-\t*\n",
+        )
+        .unwrap();
+        std::fs::write(&fasta_file, ">primer1 first primer\nGAT\ntc\n").unwrap();
+        let rows: Vec<Vec<Value>> =
+            find_rows(&file_path, &[String::from("gtgg")], Some(&fasta_file)).unwrap();
+        let no_query: io::Result<Vec<Vec<Value>>> = find_rows(&file_path, &[], None);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Both queries span a link, and rev reads them on the reverse strand
+        assert_eq!(
+            rows,
+            vec![
+                hit("gtgg", ">1>2", (2, 2), ("fwd", 2, 6, "+")),
+                hit("gtgg", ">1>2", (2, 2), ("rev", 4, 8, "-")),
+                hit("primer1", ">2>3", (1, 3), ("fwd", 5, 10, "+")),
+                hit("primer1", ">2>3", (1, 3), ("rev", 0, 5, "-")),
+            ]
+        );
+        assert!(no_query.is_err());
+    }
+}
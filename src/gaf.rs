@@ -0,0 +1,629 @@
+use crate::index_gfa_file::{linear_contig, path_offsets, read_path_offsets, StepOffset};
+use crate::walks::{parse_w_steps, read_lengths};
+use clap::ValueEnum;
+use flate2::read::MultiGzDecoder;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+pub struct GafRecord {
+    pub query_name: String,
+    pub query_length: u64,
+    pub query_start: u64,
+    pub query_end: u64,
+    // Strand of the query relative to the path, true if forward
+    pub forward: bool,
+    // Oriented segments of the alignment path (true if forward)
+    pub path: Vec<(String, bool)>,
+    pub path_start: u64,
+    pub path_end: u64,
+    pub mapping_quality: u64,
+    pub tags: Vec<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ProjectionFormat {
+    /// BED intervals: contig, start, end, query name, mapping quality and strand
+    Bed,
+    /// SAM records, with the CIGAR of the alignment when it follows the path
+    Sam,
+}
+
+pub fn open_alignments(file_path: &str) -> io::Result<Box<dyn BufRead>> {
+    /*
+    Opens a plain or gzip-compressed file, recognized by its magic number
+     */
+    let mut reader: BufReader<File> = BufReader::new(File::open(file_path)?);
+    if reader.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+pub fn parse_gaf_line(line: &str) -> io::Result<Option<GafRecord>> {
+    /*
+    Parses a line of a GAF file; comments and unmapped records (path '*') give None
+    A path that is not made of oriented segments (">"/"<") is read as a single segment
+     */
+    let columns: Vec<&str> = line.trim_end().split('\t').collect();
+    if line.starts_with('#') || line.trim().is_empty() || columns.get(5) == Some(&"*") {
+        return Ok(None);
+    }
+    if columns.len() < 12 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("GAF record with less than 12 columns: {}", line.trim_end()),
+        ));
+    }
+    let number = |column: usize| -> io::Result<u64> {
+        columns[column].parse::<u64>().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid number in column {} of GAF record: {}",
+                    column + 1,
+                    columns[column]
+                ),
+            )
+        })
+    };
+    let path: Vec<(String, bool)> = if columns[5].starts_with(['>', '<']) {
+        parse_w_steps(columns[5])?
+            .into_iter()
+            .map(|(node, orientation)| (node, orientation == '>'))
+            .collect()
+    } else {
+        vec![(columns[5].to_string(), true)]
+    };
+    Ok(Some(GafRecord {
+        query_name: columns[0].to_string(),
+        query_length: number(1)?,
+        query_start: number(2)?,
+        query_end: number(3)?,
+        forward: columns[4] != "-",
+        path,
+        path_start: number(7)?,
+        path_end: number(8)?,
+        mapping_quality: number(11)?,
+        tags: columns[12..].iter().map(|tag| tag.to_string()).collect(),
+    }))
+}
+
+pub fn coverage_gaf(
+    file_path: &str,
+    alignments: &str,
+    tracks_file: Option<&str>,
+) -> io::Result<()> {
+    /*
+    This function reads a GFA file and a GAF file (plain or gzip), and prints the coverage of each segment
+    - number of alignments going through the segment, aligned bases and mean depth
+    - tracks_file: coverage projected on the steps of each path (P-lines and W-lines), as a
+      bedGraph-like TSV with path, start, end and mean depth (offsets as given by Offsets)
+    Alignments referencing segments absent from the graph are skipped and summarized on stderr
+     */
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
+    let mut alignment_counts: HashMap<String, u64> = HashMap::new();
+    let mut aligned_bases: HashMap<String, u64> = HashMap::new();
+    let mut unknown_segments: HashMap<String, u64> = HashMap::new();
+    let mut skipped_alignments: u64 = 0;
+    let mut unmapped: u64 = 0;
+    let mut total_alignments: u64 = 0;
+
+    let mut reader: Box<dyn BufRead> = open_alignments(alignments)?;
+    let mut line: String = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        let Some(record) = parse_gaf_line(&line)? else {
+            if !line.starts_with('#') && !line.trim().is_empty() {
+                unmapped += 1;
+            }
+            line.clear();
+            continue;
+        };
+        total_alignments += 1;
+        let unknown: Vec<&String> = record
+            .path
+            .iter()
+            .map(|(node, _)| node)
+            .filter(|node| !seq_lengths.contains_key(*node))
+            .collect();
+        if !unknown.is_empty() {
+            skipped_alignments += 1;
+            for node in unknown {
+                *unknown_segments.entry(node.clone()).or_default() += 1;
+            }
+            line.clear();
+            continue;
+        }
+        // The alignment covers [path_start, path_end) of the concatenated segments
+        let mut position: u64 = 0;
+        for (node, _) in record.path.iter() {
+            let length: u64 = seq_lengths[node];
+            let start: u64 = record.path_start.max(position);
+            let end: u64 = record.path_end.min(position + length);
+            if start < end {
+                *alignment_counts.entry(node.clone()).or_default() += 1;
+                *aligned_bases.entry(node.clone()).or_default() += end - start;
+            }
+            position += length;
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    let depth = |node: &str| -> f64 {
+        match (aligned_bases.get(node), seq_lengths.get(node)) {
+            (Some(bases), Some(length)) if *length > 0 => *bases as f64 / *length as f64,
+            _ => 0.0,
+        }
+    };
+
+    // Segments are reported in the order of the graph
+    let file: File = File::open(file_path)?;
+    let mut gfa: BufReader<File> = BufReader::new(file);
+    println!("# NodeName\tLength\tAlignments\tAlignedBases\tMeanDepth");
+    while gfa.read_line(&mut line)? > 0 {
+        if line.starts_with('S') {
+            let node: &str = line.split('\t').nth(1).unwrap_or_default();
+            println!(
+                "{}\t{}\t{}\t{}\t{:.4}",
+                node,
+                seq_lengths[node],
+                alignment_counts.get(node).unwrap_or(&0),
+                aligned_bases.get(node).unwrap_or(&0),
+                depth(node)
+            );
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    if let Some(tracks_file) = tracks_file {
+        let output_file: File = File::create(tracks_file)?;
+        let mut file_writer: BufWriter<File> = BufWriter::new(output_file);
+        writeln!(file_writer, "# Path\tStart\tEnd\tMeanDepth")?;
+        let file: File = File::open(file_path)?;
+        let mut gfa: BufReader<File> = BufReader::new(file);
+        while gfa.read_line(&mut line)? > 0 {
+            let columns: Vec<&str> = line.trim_end().split('\t').collect();
+            if let Some((path_name, offsets)) = path_offsets(&columns, &seq_lengths)? {
+                for step in offsets {
+                    writeln!(
+                        file_writer,
+                        "{}\t{}\t{}\t{:.4}",
+                        path_name,
+                        step.start,
+                        step.end,
+                        depth(&step.node)
+                    )?;
+                }
+            }
+            line.clear(); // Clear the line buffer for the next read
+        }
+        file_writer.flush()?;
+    }
+
+    eprintln!(
+        "{} alignments, {} unmapped records, {} alignments skipped for referencing {} unknown segments",
+        total_alignments,
+        unmapped,
+        skipped_alignments,
+        unknown_segments.len()
+    );
+    let mut unknown_segments: Vec<(String, u64)> = unknown_segments.into_iter().collect();
+    unknown_segments.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    for (node, count) in unknown_segments.iter().take(10) {
+        eprintln!("Unknown segment {} in {} alignments", node, count);
+    }
+
+    Ok(())
+}
+
+struct Projection {
+    start: u64,
+    end: u64,
+    // True if the alignment path follows the reference in the same direction
+    same_direction: bool,
+    // True if the aligned segments are consecutive steps of the reference
+    contiguous: bool,
+}
+
+pub fn surject_gaf(
+    file_path: &str,
+    alignments: &str,
+    reference: &str,
+    format: ProjectionFormat,
+) -> io::Result<()> {
+    /*
+    This function reads a GFA file and a GAF file (plain or gzip), and projects alignments on a reference path
+    Positions are the offsets of the path (as given by Offsets), on the contig of its PanSN name
+    - each aligned segment found on the reference is placed on one of its occurrences, the one following
+      the previous segment when the reference visits it several times
+    - the interval spans the segments placed in the main direction of the alignment on the reference
+    - SAM records keep the CIGAR (cg tag) only when the aligned segments are consecutive steps of the reference
+    Alignments sharing no segment with the reference, or with no path ('*'), are unmapped (skipped in BED)
+     */
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    write_projections(file_path, alignments, reference, format, &mut writer)?;
+    writer.flush()
+}
+
+fn write_projections<W: Write>(
+    file_path: &str,
+    alignments: &str,
+    reference: &str,
+    format: ProjectionFormat,
+    writer: &mut W,
+) -> io::Result<()> {
+    /*
+    Writes the projections of the alignments on the reference to the given writer (see surject_gaf)
+     */
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
+    let steps: Vec<StepOffset> = read_path_offsets(file_path, &seq_lengths, reference)?;
+    let contig: String = linear_contig(reference);
+    let mut occurrences: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, step) in steps.iter().enumerate() {
+        occurrences
+            .entry(step.node.as_str())
+            .or_default()
+            .push(index);
+    }
+
+    if format == ProjectionFormat::Sam {
+        writeln!(writer, "@HD\tVN:1.6\tSO:unsorted")?;
+        writeln!(
+            writer,
+            "@SQ\tSN:{}\tLN:{}",
+            contig,
+            steps.last().map_or(0, |step| step.end)
+        )?;
+    }
+    let mut reader: Box<dyn BufRead> = open_alignments(alignments)?;
+    let mut line: String = String::new();
+    let (mut projected, mut unmapped): (u64, u64) = (0, 0);
+    while reader.read_line(&mut line)? > 0 {
+        let Some(record) = parse_gaf_line(&line)? else {
+            // Records without path are unmapped
+            if !line.starts_with('#') && !line.trim().is_empty() {
+                unmapped += 1;
+                if format == ProjectionFormat::Sam {
+                    writeln!(
+                        writer,
+                        "{}\t4\t*\t0\t0\t*\t*\t0\t0\t*\t*",
+                        line.split('\t').next().unwrap_or_default()
+                    )?;
+                }
+            }
+            line.clear();
+            continue;
+        };
+        line.clear(); // Clear the line buffer for the next read
+        let Some(projection) = project(&record, &steps, &occurrences, &seq_lengths) else {
+            unmapped += 1;
+            if format == ProjectionFormat::Sam {
+                writeln!(
+                    writer,
+                    "{}\t4\t*\t0\t0\t*\t*\t0\t0\t*\t*",
+                    record.query_name
+                )?;
+            }
+            continue;
+        };
+        projected += 1;
+        let reverse: bool = record.forward != projection.same_direction;
+        match format {
+            ProjectionFormat::Bed => writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                contig,
+                projection.start,
+                projection.end,
+                record.query_name,
+                record.mapping_quality,
+                if reverse { '-' } else { '+' }
+            )?,
+            ProjectionFormat::Sam => {
+                let cigar: String = if projection.contiguous {
+                    linear_cigar(&record, projection.same_direction).unwrap_or(String::from("*"))
+                } else {
+                    String::from("*")
+                };
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}\t{}\t{}\t*\t0\t0\t*\t*",
+                    record.query_name,
+                    if reverse { 16 } else { 0 },
+                    contig,
+                    projection.start + 1,
+                    record.mapping_quality.min(255),
+                    cigar
+                )?;
+            }
+        }
+    }
+    eprintln!(
+        "{} alignments projected on {}, {} unmapped",
+        projected, reference, unmapped
+    );
+    Ok(())
+}
+
+fn project(
+    record: &GafRecord,
+    steps: &[StepOffset],
+    occurrences: &HashMap<&str, Vec<usize>>,
+    seq_lengths: &HashMap<String, u64>,
+) -> Option<Projection> {
+    /*
+    Places the aligned part of each segment of the alignment on the reference
+    Returns None if no aligned segment is on the reference
+     */
+    // Step of the reference, direction and interval on the reference of each aligned segment
+    let mut pieces: Vec<(usize, bool, u64, u64)> = Vec::new();
+    let mut aligned_segments: usize = 0;
+    let mut position: u64 = 0;
+    for (node, forward) in record.path.iter() {
+        let length: u64 = *seq_lengths.get(node)?;
+        let (start, end) = (
+            record.path_start.max(position),
+            record.path_end.min(position + length),
+        );
+        let (local_start, local_end) =
+            (start.saturating_sub(position), end.saturating_sub(position));
+        position += length;
+        if start >= end {
+            continue;
+        }
+        aligned_segments += 1;
+        let Some(candidates) = occurrences.get(node.as_str()) else {
+            continue;
+        };
+        // The occurrence closest to the one following the previous piece is chosen
+        let expected: Option<usize> = pieces.last().map(|(index, same_direction, _, _)| {
+            if *same_direction {
+                index + 1
+            } else {
+                index.saturating_sub(1)
+            }
+        });
+        let index: usize = *candidates
+            .iter()
+            .min_by_key(|candidate| expected.map_or(0, |expected| candidate.abs_diff(expected)))?;
+        let step: &StepOffset = &steps[index];
+        let same_direction: bool = *forward == (step.orientation == '+');
+        let (start, end) = if same_direction {
+            (step.start + local_start, step.start + local_end)
+        } else {
+            (step.end - local_end, step.end - local_start)
+        };
+        pieces.push((index, same_direction, start, end));
+    }
+    // The main direction is the one of most aligned bases
+    let forward_bases: u64 = pieces
+        .iter()
+        .filter(|(_, same_direction, _, _)| *same_direction)
+        .map(|(_, _, start, end)| end - start)
+        .sum();
+    let total_bases: u64 = pieces.iter().map(|(_, _, start, end)| end - start).sum();
+    let same_direction: bool = 2 * forward_bases >= total_bases;
+    let kept: Vec<&(usize, bool, u64, u64)> = pieces
+        .iter()
+        .filter(|(_, direction, _, _)| *direction == same_direction)
+        .collect();
+    let contiguous: bool = kept.len() == aligned_segments
+        && kept.windows(2).all(|window| {
+            if same_direction {
+                window[1].0 == window[0].0 + 1
+            } else {
+                window[1].0 + 1 == window[0].0
+            }
+        });
+    Some(Projection {
+        start: kept.iter().map(|(_, _, start, _)| *start).min()?,
+        end: kept.iter().map(|(_, _, _, end)| *end).max()?,
+        same_direction,
+        contiguous,
+    })
+}
+
+fn linear_cigar(record: &GafRecord, same_direction: bool) -> Option<String> {
+    /*
+    CIGAR of the alignment on the reference, from the cg tag, with soft clips for unaligned query bases
+    The CIGAR of the GAF follows the path: it is reversed when the path is reversed on the reference
+     */
+    let cigar: &str = record
+        .tags
+        .iter()
+        .find_map(|tag| tag.strip_prefix("cg:Z:"))?;
+    let mut operations: Vec<(u64, char)> = Vec::new();
+    let mut length: String = String::new();
+    for character in cigar.chars() {
+        if character.is_ascii_digit() {
+            length.push(character);
+        } else {
+            operations.push((length.parse::<u64>().ok()?, character));
+            length.clear();
+        }
+    }
+    // Clips are given in the direction of the path
+    let (mut left_clip, mut right_clip) = (
+        record.query_start,
+        record.query_length.saturating_sub(record.query_end),
+    );
+    if !record.forward {
+        (left_clip, right_clip) = (right_clip, left_clip);
+    }
+    if !same_direction {
+        operations.reverse();
+        (left_clip, right_clip) = (right_clip, left_clip);
+    }
+    let mut linear: String = String::new();
+    if left_clip > 0 {
+        linear += &format!("{}S", left_clip);
+    }
+    for (length, operation) in operations {
+        linear += &format!("{}{}", length, operation);
+    }
+    if right_clip > 0 {
+        linear += &format!("{}S", right_clip);
+    }
+    Some(linear)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    #[test]
+    fn test_coverage_gaf() {
+        let directory: std::path::PathBuf = temp_dir("coverage");
+        let file_path: String = directory.join("graph.gfa").to_string_lossy().into_owned();
+        let alignments: String = directory
+            .join("reads.gaf.gz")
+            .to_string_lossy()
+            .into_owned();
+        let tracks_file: String = directory.join("tracks.tsv").to_string_lossy().into_owned();
+        std::fs::write(
+            &file_path,
+            "S\t1\tACGT\nS\t2\tGG\nS\t3\tTTTT\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nP\tref\t1+,2+,3+\t*\n",
+        )
+        .unwrap();
+        let mut encoder: GzEncoder<File> =
+            GzEncoder::new(File::create(&alignments).unwrap(), Compression::default());
+        encoder
+            .write_all(
+                b"read1\t4\t0\t4\t+\t>1>2\t6\t2\t6\t4\t4\t60\n\
+                read2\t4\t0\t4\t+\t<3\t4\t0\t4\t4\t4\t60\n\
+                read3\t4\t0\t4\t+\t>3>9\t8\t0\t4\t4\t4\t60\n\
+                read4\t4\t*\t*\t*\t*\t*\t*\t*\t*\t*\t255\n",
+            )
+            .unwrap();
+        encoder.finish().unwrap();
+        coverage_gaf(&file_path, &alignments, Some(&tracks_file)).unwrap();
+        let tracks: String = std::fs::read_to_string(&tracks_file).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        // read3 references an unknown segment and is skipped
+        assert_eq!(
+            tracks,
+            "# Path\tStart\tEnd\tMeanDepth\nref\t0\t4\t0.5000\nref\t4\t6\t1.0000\nref\t6\t10\t1.0000\n"
+        );
+    }
+
+    #[test]
+    fn test_write_projections() {
+        let directory: std::path::PathBuf = temp_dir("test_surject");
+        let file_path: String = directory.join("graph.gfa").to_string_lossy().into_owned();
+        let alignments: String = directory.join("reads.gaf").to_string_lossy().into_owned();
+        // The reference starts at position 100 of chr1
+        std::fs::write(
+            &file_path,
+            "S\t1\tACGT\nS\t2\tGG\nS\t3\tTTAA\nP\tHG1#1#chr1:100-110\t1+,2+,3+\t*\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &alignments,
+            "read1\t4\t0\t4\t+\t>1>2\t6\t2\t6\t4\t4\t60\n\
+             read2\t4\t*\t*\t*\t*\t*\t*\t*\t*\t*\t255\n",
+        )
+        .unwrap();
+        let project = |format: ProjectionFormat| -> String {
+            let mut output: Vec<u8> = Vec::new();
+            write_projections(
+                &file_path,
+                &alignments,
+                "HG1#1#chr1:100-110",
+                format,
+                &mut output,
+            )
+            .unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let bed: String = project(ProjectionFormat::Bed);
+        let sam: String = project(ProjectionFormat::Sam);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(bed, "chr1\t102\t106\tread1\t60\t+\n");
+        // Records without path are written as unmapped
+        assert_eq!(
+            sam,
+            "@HD\tVN:1.6\tSO:unsorted\n@SQ\tSN:chr1\tLN:110\n\
+             read1\t0\tchr1\t103\t60\t*\t*\t0\t0\t*\t*\n\
+             read2\t4\t*\t0\t0\t*\t*\t0\t0\t*\t*\n"
+        );
+    }
+
+    fn record(line: &str) -> GafRecord {
+        parse_gaf_line(line).unwrap().unwrap()
+    }
+
+    fn reference() -> (Vec<StepOffset>, HashMap<String, u64>) {
+        let seq_lengths: HashMap<String, u64> = [("1", 4), ("2", 2), ("3", 3), ("4", 5), ("5", 1)]
+            .iter()
+            .map(|(node, length)| (node.to_string(), *length))
+            .collect();
+        // 2 is visited twice, at [4, 6) and [9, 11)
+        let (_, steps) = path_offsets(&["P", "ref", "1+,2+,3+,2+,4+", "*"], &seq_lengths)
+            .unwrap()
+            .unwrap();
+        (steps, seq_lengths)
+    }
+
+    #[test]
+    fn test_project() {
+        let (steps, seq_lengths) = reference();
+        let mut occurrences: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (index, step) in steps.iter().enumerate() {
+            occurrences
+                .entry(step.node.as_str())
+                .or_default()
+                .push(index);
+        }
+        let projection = |line: &str| -> Option<(u64, u64, bool, bool)> {
+            project(&record(line), &steps, &occurrences, &seq_lengths).map(|projection| {
+                (
+                    projection.start,
+                    projection.end,
+                    projection.same_direction,
+                    projection.contiguous,
+                )
+            })
+        };
+        // Partly aligned first segment, then the first visit of 2
+        assert_eq!(
+            projection("r1\t4\t0\t4\t+\t>1>2\t6\t2\t6\t4\t4\t60"),
+            Some((2, 6, true, true))
+        );
+        // 2 is placed on the visit following 3
+        assert_eq!(
+            projection("r2\t10\t0\t10\t+\t>3>2>4\t10\t0\t10\t10\t10\t60"),
+            Some((6, 16, true, true))
+        );
+        // Path reversed on the reference
+        assert_eq!(
+            projection("r3\t6\t0\t6\t+\t<2<1\t6\t0\t6\t6\t6\t60"),
+            Some((0, 6, false, true))
+        );
+        // Segments that are not consecutive on the reference
+        assert_eq!(
+            projection("r4\t6\t0\t6\t+\t>1>4\t9\t0\t9\t9\t9\t60"),
+            Some((0, 16, true, false))
+        );
+        // No segment on the reference
+        assert_eq!(projection("r5\t1\t0\t1\t+\t>5\t1\t0\t1\t1\t1\t60"), None);
+    }
+
+    #[test]
+    fn test_linear_cigar() {
+        let forward: GafRecord = record("r1\t10\t2\t9\t+\t>1>2\t6\t0\t6\t6\t7\t60\tcg:Z:5M1I1M");
+        assert_eq!(linear_cigar(&forward, true).as_deref(), Some("2S5M1I1M1S"));
+        // Path reversed on the reference: operations and clips are reversed
+        assert_eq!(linear_cigar(&forward, false).as_deref(), Some("1S1M1I5M2S"));
+        // Query reversed on the path: clips are given from the end of the query
+        let reverse: GafRecord = record("r2\t10\t2\t9\t-\t>1>2\t6\t0\t6\t6\t7\t60\tcg:Z:5M1I1M");
+        assert_eq!(linear_cigar(&reverse, true).as_deref(), Some("1S5M1I1M2S"));
+        let without_cigar: GafRecord = record("r3\t6\t0\t6\t+\t>1>2\t6\t0\t6\t6\t6\t60");
+        assert_eq!(linear_cigar(&without_cigar, true), None);
+    }
+}
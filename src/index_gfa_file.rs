@@ -1,7 +1,12 @@
-use std::collections::HashMap;
+use crate::remove_loops::count_occurences;
+use crate::table::{ColumnType, OutputFormat, TableWriter, Value};
+use crate::walks::{parse_p_steps, parse_w_steps, read_lengths, split_pansn};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
 
+#[allow(dead_code)]
 pub fn find_spurious_breakpoints(file_path: &str) -> io::Result<()> {
     /*
     Given a file path, this function reads the GFA file and returns a HashMap:
@@ -48,177 +53,751 @@ pub fn find_spurious_breakpoints(file_path: &str) -> io::Result<()> {
     Ok(())
 }
 
-pub fn index_gfa(file_path: &str) -> io::Result<()> {
+#[derive(Default)]
+struct SegmentInfo {
+    length: u64,
+    n_count: u64,
+    gc_count: u64,
+    // Number of distinct paths visiting the segment, and last path seen while counting them
+    path_count: usize,
+    last_path: usize,
+}
+
+type Adjacency = (String, char, String, char);
+
+struct PathLine {
+    name: String,
+    sample: String,
+    haplotype: String,
+    steps: Vec<(String, char)>,
+}
+
+pub fn index_gfa(file_path: &str, reference: Option<&str>, format: OutputFormat) -> io::Result<()> {
+    let rows: Vec<Vec<Value>> = index_rows(file_path, reference)?;
+    let mut table: TableWriter = TableWriter::new(
+        format,
+        &[
+            ("PathName", ColumnType::Text),
+            ("Sample", ColumnType::Text),
+            ("Haplotype", ColumnType::Text),
+            ("Length", ColumnType::Integer),
+            ("ForwardLength", ColumnType::Integer),
+            ("ReverseLength", ColumnType::Integer),
+            ("Steps", ColumnType::Integer),
+            ("DistinctSegments", ColumnType::Integer),
+            ("RepeatedVisits", ColumnType::Integer),
+            ("NContent", ColumnType::Float),
+            ("GCContent", ColumnType::Float),
+            ("Breakpoints", ColumnType::Integer),
+            ("PrivateLength", ColumnType::Integer),
+        ],
+    )?;
+    for row in rows {
+        table.write_row(row)?;
+    }
+    table.finish()
+}
+
+fn index_rows(file_path: &str, reference: Option<&str>) -> io::Result<Vec<Vec<Value>>> {
     /*
-    This function reads a GFA file and prints the length of each path
+    This function reads a GFA file and prints statistics on each path (P-lines and W-lines)
+    - sample and haplotype, from the PanSN name of P-lines or the fields of W-lines
+    - length, and length in forward and reverse orientation
+    - number of steps, of distinct segments, and of repeated visits to a segment (loops)
+    - N content, and GC content of the non-N bases
+    - breakpoints: adjacencies of the path that are not adjacencies of the reference path
+    - private length: bp of the segments visited by no other path
+    A first pass gathers segments, the number of paths through each one and the reference
      */
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
+    let mut segments: HashMap<String, SegmentInfo> = HashMap::new();
+    let mut reference_adjacencies: Option<HashSet<Adjacency>> = None;
+
     let file: File = File::open(file_path)?;
     let mut reader: BufReader<File> = BufReader::new(file);
-    let mut seq_lengths: HashMap<String, u64> = HashMap::new();
     let mut line: String = String::new();
+    let mut path_index: usize = 0;
 
-    println!(
-        "# {}\t{}\t{}\t{}",
-        "PathName", "Length", "ForwardLength", "ReverseLength"
-    );
     while reader.read_line(&mut line)? > 0 {
-        let columns: Vec<&str> = line.split('\t').collect();
-        if let Some(first_char) = line.chars().next() {
-            if first_char == 'S' {
-                // In the case of an S-line, we store the node name and the sequence length
-                let node_name: String = String::from(columns[1]);
-                let sequence_length: u64 = columns[2].trim().len() as u64;
-                seq_lengths.insert(node_name, sequence_length);
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        if line.starts_with('S') {
+            let sequence: &[u8] = columns[2].as_bytes();
+            let segment: &mut SegmentInfo = segments.entry(columns[1].to_string()).or_default();
+            // Segments without sequence ('*') take their length from their LN tag
+            segment.length = seq_lengths[columns[1]];
+            segment.n_count = sequence
+                .iter()
+                .filter(|base| matches!(base, b'N' | b'n'))
+                .count() as u64;
+            segment.gc_count = sequence
+                .iter()
+                .filter(|base| matches!(base, b'G' | b'C' | b'g' | b'c'))
+                .count() as u64;
+        } else if let Some(path) = read_path(&columns)? {
+            path_index += 1;
+            for (node, _) in path.steps.iter() {
+                let segment: &mut SegmentInfo = segments.entry(node.to_string()).or_default();
+                if segment.last_path != path_index {
+                    segment.last_path = path_index;
+                    segment.path_count += 1;
+                }
             }
-            if first_char == 'P' {
-                let mut path_length: u64 = 0;
-                let mut path_length_forward: u64 = 0;
-                let mut path_length_reverse: u64 = 0;
-                let path_name: String = String::from(columns[1]);
-
-                let node_list: Vec<String> = columns[2]
-                    .trim()
-                    .split(',')
-                    .map(|s| s.to_string())
-                    .collect();
-                for node_desc in node_list {
-                    let (node, orientation) = node_desc.split_at(node_desc.len() - 1);
-                    let sequence_length: &u64 = seq_lengths.get(node).unwrap();
-                    path_length += sequence_length;
-                    if orientation == "+" {
-                        path_length_forward += sequence_length;
-                    } else {
-                        path_length_reverse += sequence_length;
-                    }
+            if reference == Some(path.name.as_str()) {
+                reference_adjacencies = Some(adjacencies(&path.steps).collect());
+            }
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+    if let (Some(reference), None) = (reference, &reference_adjacencies) {
+        eprintln!("Reference {} not found in graph", reference);
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Path not found in graph: {}", reference),
+        ));
+    }
+
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+
+    let mut rows: Vec<Vec<Value>> = Vec::new();
+    while reader.read_line(&mut line)? > 0 {
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        if let Some(path) = read_path(&columns)? {
+            let mut path_length: u64 = 0;
+            let mut path_length_forward: u64 = 0;
+            let mut path_length_reverse: u64 = 0;
+            let mut n_count: u64 = 0;
+            let mut gc_count: u64 = 0;
+            let mut private_length: u64 = 0;
+
+            for (node, orientation) in path.steps.iter() {
+                let segment: &SegmentInfo = segments.get(node).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("Segment not found in graph: {}", node),
+                    )
+                })?;
+                path_length += segment.length;
+                if *orientation == '+' {
+                    path_length_forward += segment.length;
+                } else {
+                    path_length_reverse += segment.length;
+                }
+                n_count += segment.n_count;
+                gc_count += segment.gc_count;
+            }
+            let occurences: HashMap<String, u8> =
+                count_occurences(path.steps.iter().map(|(node, _)| node.as_str()));
+            // Private segments are only counted once, even if the path visits them several times
+            for node in occurences.keys() {
+                if segments[node].path_count == 1 {
+                    private_length += segments[node].length;
                 }
-                println!(
-                    "{}\t{}\t{}\t{}",
-                    path_name, path_length, path_length_forward, path_length_reverse
-                );
             }
+            let breakpoints: Value = match &reference_adjacencies {
+                Some(reference_adjacencies) => Value::Integer(
+                    adjacencies(&path.steps)
+                        .filter(|adjacency| !reference_adjacencies.contains(adjacency))
+                        .count() as i64,
+                ),
+                None => Value::Missing,
+            };
+            rows.push(vec![
+                Value::Text(path.name),
+                Value::Text(path.sample),
+                Value::Text(path.haplotype),
+                Value::Integer(path_length as i64),
+                Value::Integer(path_length_forward as i64),
+                Value::Integer(path_length_reverse as i64),
+                Value::Integer(path.steps.len() as i64),
+                Value::Integer(occurences.len() as i64),
+                Value::Integer((path.steps.len() - occurences.len()) as i64),
+                Value::Float(ratio(n_count, path_length)),
+                Value::Float(ratio(gc_count, path_length - n_count)),
+                breakpoints,
+                Value::Integer(private_length as i64),
+            ]);
         }
         line.clear(); // Clear the line buffer for the next read
     }
 
-    Ok(())
+    Ok(rows)
 }
 
-pub fn rename_paths(file_path: &str, rename_file: &str) -> io::Result<()> {
+fn read_path(columns: &[&str]) -> io::Result<Option<PathLine>> {
     /*
-    This function reads a GFA file and a TSV file with two columns: old_name and new_name
-    It replaces the names of the paths in the GFA file with the new names
+    Name, sample, haplotype and steps (with '+'/'-' orientations) of a P-line or a W-line
+    W-lines are named after their PanSN name (sample#haplotype#seqid)
+    Sample and haplotype are '.' for P-lines that are not named following PanSN
+     */
+    match columns[0] {
+        "P" => {
+            let (sample, haplotype) = match split_pansn(columns[1]) {
+                Some(pansn) => (pansn.sample.to_string(), pansn.haplotype.to_string()),
+                None => (String::from("."), String::from(".")),
+            };
+            Ok(Some(PathLine {
+                name: columns[1].to_string(),
+                sample,
+                haplotype,
+                steps: parse_p_steps(columns[2])?,
+            }))
+        }
+        "W" => {
+            let steps: Vec<(String, char)> = parse_w_steps(columns[6])?
+                .into_iter()
+                .map(|(node, orientation)| (node, if orientation == '>' { '+' } else { '-' }))
+                .collect();
+            Ok(Some(PathLine {
+                name: format!("{}#{}#{}", columns[1], columns[2], columns[3]),
+                sample: columns[1].to_string(),
+                haplotype: columns[2].to_string(),
+                steps,
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn adjacencies(steps: &[(String, char)]) -> impl Iterator<Item = Adjacency> + '_ {
+    /*
+    Adjacencies between consecutive steps, written in a canonical orientation
+    so that an adjacency read on the reverse strand matches the forward one
+     */
+    steps.windows(2).map(|window| {
+        let (from, from_orientation) = &window[0];
+        let (to, to_orientation) = &window[1];
+        let forward: Adjacency = (from.clone(), *from_orientation, to.clone(), *to_orientation);
+        let reverse: Adjacency = (
+            to.clone(),
+            if *to_orientation == '+' { '-' } else { '+' },
+            from.clone(),
+            if *from_orientation == '+' { '-' } else { '+' },
+        );
+        forward.min(reverse)
+    })
+}
+
+fn ratio(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+pub fn rename_paths(
+    file_path: &str,
+    rename_file: Option<&str>,
+    pattern: Option<&str>,
+    template: &str,
+    segments_file: Option<&str>,
+) -> io::Result<()> {
+    /*
+    This function reads a GFA file and renames its paths (P-lines) and walks (W-lines)
+    - rename_file: a TSV file with two columns, old_name and new_name
+    - pattern and template: a regex applied to path names, and its replacement (e.g. '$1#$2#$3')
+    Names listed in the TSV file take precedence over the pattern, other paths are kept unchanged
+    W-lines are renamed through their PanSN name (sample#haplotype#seqid)
+    - segments_file: a TSV file with two columns, old_id and new_id, to rename segments as well
      */
     let file: File = File::open(file_path)?;
     let mut reader: BufReader<File> = BufReader::new(file);
     let mut line: String = String::new();
 
-    let rename_file: File = File::open(rename_file)?;
-    let mut rename_reader: BufReader<File> = BufReader::new(rename_file);
-    let mut rename_line: String = String::new();
-    let mut rename_map: HashMap<String, String> = HashMap::new();
+    let rename_map: HashMap<String, String> = match rename_file {
+        Some(rename_file) => read_mapping(rename_file)?,
+        None => HashMap::new(),
+    };
+    let segment_map: HashMap<String, String> = match segments_file {
+        Some(segments_file) => read_mapping(segments_file)?,
+        None => HashMap::new(),
+    };
+    let regex: Option<Regex> = match pattern {
+        Some(pattern) => Some(
+            Regex::new(pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+        ),
+        None => None,
+    };
+    let rename = |name: &str| -> String {
+        if let Some(new_name) = rename_map.get(name) {
+            new_name.clone()
+        } else if let Some(regex) = &regex {
+            regex.replace(name, template).to_string()
+        } else {
+            name.to_string()
+        }
+    };
+    let rename_segment = |segment: &str| -> String {
+        segment_map
+            .get(segment)
+            .cloned()
+            .unwrap_or_else(|| segment.to_string())
+    };
+
+    // W-lines are checked before anything is printed, as their new name must be a PanSN name
+    while reader.read_line(&mut line)? > 0 {
+        if line.starts_with('W') {
+            rename_line(&line, &rename, &rename_segment, !segment_map.is_empty())?;
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    while reader.read_line(&mut line)? > 0 {
+        println!(
+            "{}",
+            rename_line(&line, &rename, &rename_segment, !segment_map.is_empty())?
+        );
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    Ok(())
+}
+
+fn rename_line(
+    line: &str,
+    rename: &dyn Fn(&str) -> String,
+    rename_segment: &dyn Fn(&str) -> String,
+    rename_segments: bool,
+) -> io::Result<String> {
+    /*
+    Renames the path of a P-line or a W-line, and the segments of S, L, C, J, P and W-lines
+    if rename_segments is set; other lines are returned unchanged (without their line break)
+     */
+    let mut columns: Vec<String> = line
+        .trim_end_matches(['\n', '\r'])
+        .split('\t')
+        .map(String::from)
+        .collect();
+    match line.chars().next() {
+        Some('P') => {
+            columns[1] = rename(&columns[1]);
+            if rename_segments {
+                columns[2] = columns[2]
+                    .split(',')
+                    .map(|node_desc| {
+                        let (node, orientation) = node_desc.split_at(node_desc.len() - 1);
+                        rename_segment(node) + orientation
+                    })
+                    .collect::<Vec<String>>()
+                    .join(",");
+            }
+        }
+        Some('W') => {
+            let path_name: String = format!("{}#{}#{}", columns[1], columns[2], columns[3]);
+            let new_name: String = rename(&path_name);
+            let fields: Vec<&str> = new_name.split('#').collect();
+            if fields.len() != 3 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "W-line {} renamed to {}, which is not a sample#haplotype#seqid name",
+                        path_name, new_name
+                    ),
+                ));
+            }
+            let (sample, haplotype, seqid) = (fields[0], fields[1], fields[2]);
+            columns[1] = sample.to_string();
+            columns[2] = haplotype.to_string();
+            columns[3] = seqid.to_string();
+            if rename_segments {
+                let mut walk: String = String::new();
+                let mut node: String = String::new();
+                for c in columns[6].chars() {
+                    if c == '>' || c == '<' {
+                        if !node.is_empty() {
+                            walk += &rename_segment(&node);
+                            node.clear();
+                        }
+                        walk.push(c);
+                    } else {
+                        node.push(c);
+                    }
+                }
+                walk += &rename_segment(&node);
+                columns[6] = walk;
+            }
+        }
+        Some('S') if rename_segments => {
+            columns[1] = rename_segment(&columns[1]);
+        }
+        Some('L') | Some('C') | Some('J') if rename_segments => {
+            columns[1] = rename_segment(&columns[1]);
+            columns[3] = rename_segment(&columns[3]);
+        }
+        _ => return Ok(line.trim_end_matches('\n').to_string()),
+    }
+    Ok(columns.join("\t"))
+}
+
+fn read_mapping(mapping_file: &str) -> io::Result<HashMap<String, String>> {
+    /*
+    Reads a tab-separated file with two columns: old_name and new_name
+     */
+    let file: File = File::open(mapping_file)?;
+    let reader: BufReader<File> = BufReader::new(file);
+    let mut mapping: HashMap<String, String> = HashMap::new();
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line: String = line?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once('\t') {
+            Some((old_name, new_name)) => {
+                mapping.insert(old_name.to_string(), new_name.trim_end().to_string());
+            }
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Line {} of {} does not have two tab-separated columns",
+                        line_number + 1,
+                        mapping_file
+                    ),
+                ))
+            }
+        }
+    }
+    Ok(mapping)
+}
+
+pub struct StepOffset {
+    pub node: String,
+    pub start: u64,
+    pub end: u64,
+    pub orientation: char,
+}
 
-    // Read the rename file and store the old and new names in a hashmap
-    while rename_reader.read_line(&mut rename_line)? > 0 {
-        let columns: Vec<&str> = rename_line.split('\t').collect();
-        let old_name: String = String::from(columns[0]);
-        let new_name: String = String::from(columns[1].strip_suffix("\n").unwrap());
-        rename_map.insert(old_name, new_name);
-        rename_line.clear();
+pub fn path_offsets(
+    columns: &[&str],
+    seq_lengths: &HashMap<String, u64>,
+) -> io::Result<Option<(String, Vec<StepOffset>)>> {
+    /*
+    Given the columns of a P-line or a W-line, returns the name of the path and the offsets of its steps
+    Offsets are 0-based and half-open, on the contig of the path: W-lines start at their start
+    position, and P-lines at the start of the range of their PanSN name (0 without range)
+    W-lines are named after their PanSN name (sample#haplotype#seqid), orientations are '+' or '-'
+     */
+    let (path_name, steps, mut position): (String, Vec<(String, char)>, u64) = match columns[0] {
+        "P" => (
+            columns[1].to_string(),
+            parse_p_steps(columns[2])?,
+            split_pansn(columns[1])
+                .and_then(|pansn| pansn.range)
+                .map_or(0, |(start, _)| start),
+        ),
+        "W" => (
+            format!("{}#{}#{}", columns[1], columns[2], columns[3]),
+            parse_w_steps(columns[6])?
+                .into_iter()
+                .map(|(node, orientation)| (node, if orientation == '>' { '+' } else { '-' }))
+                .collect(),
+            columns[4].parse::<u64>().unwrap_or(0),
+        ),
+        _ => return Ok(None),
+    };
+    let mut offsets: Vec<StepOffset> = Vec::with_capacity(steps.len());
+    for (node, orientation) in steps {
+        let sequence_length: u64 = *seq_lengths.get(&node).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Segment not found in graph: {}", node),
+            )
+        })?;
+        offsets.push(StepOffset {
+            node,
+            start: position,
+            end: position + sequence_length,
+            orientation,
+        });
+        position += sequence_length;
     }
+    Ok(Some((path_name, offsets)))
+}
 
+pub fn read_path_offsets(
+    file_path: &str,
+    seq_lengths: &HashMap<String, u64>,
+    path_name: &str,
+) -> io::Result<Vec<StepOffset>> {
+    /*
+    Reads a GFA file until the given path (P-line or W-line) and returns the offsets of its steps
+     */
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line: String = String::new();
     while reader.read_line(&mut line)? > 0 {
-        let columns: Vec<&str> = line.split('\t').collect();
-        if let Some(first_char) = line.chars().next() {
-            if first_char == 'P' {
-                println!(
-                    "{}\t{}\t{}",
-                    columns[0],
-                    rename_map.get(columns[1]).unwrap(),
-                    columns[2..].join("\t")
-                );
-            } else {
-                println!("{}", line);
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        if let Some((name, offsets)) = path_offsets(&columns, seq_lengths)? {
+            if name == path_name {
+                return Ok(offsets);
             }
         }
         line.clear(); // Clear the line buffer for the next read
     }
+    eprintln!("Reference {} not found in graph", path_name);
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Path not found in graph: {}", path_name),
+    ))
+}
 
-    Ok(())
+pub fn linear_contig(path_name: &str) -> String {
+    /*
+    Contig of a path in linear coordinates, on which its offsets are given (see path_offsets)
+    A PanSN name gives its contig, other paths are their own contig
+     */
+    match split_pansn(path_name) {
+        Some(pansn) => pansn.contig.to_string(),
+        None => path_name.to_string(),
+    }
 }
 
-pub fn offset_gfa(file_path: &str) -> io::Result<()> {
+pub fn offset_gfa(file_path: &str, format: OutputFormat) -> io::Result<()> {
     /*
-    This function reads a GFA file and prints the length of each path
+    This function reads a GFA file and prints the position of each step of each path (P-lines and W-lines)
      */
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
     let file: File = File::open(file_path)?;
     let mut reader: BufReader<File> = BufReader::new(file);
-    let mut seq_lengths: HashMap<String, u64> = HashMap::new();
     let mut line: String = String::new();
 
-    println!(
-        "# {}\t{}\t{}\t{}\t{}\t{}",
-        "NodeName", "Path", "StartPos", "EndPos", "Length", "Orientation"
-    );
+    let mut table: TableWriter = TableWriter::new(
+        format,
+        &[
+            ("NodeName", ColumnType::Text),
+            ("Path", ColumnType::Text),
+            ("StartPos", ColumnType::Integer),
+            ("EndPos", ColumnType::Integer),
+            ("Length", ColumnType::Integer),
+            ("Orientation", ColumnType::Text),
+        ],
+    )?;
     while reader.read_line(&mut line)? > 0 {
-        let columns: Vec<&str> = line.split('\t').collect();
-        if let Some(first_char) = line.chars().next() {
-            if first_char == 'S' {
-                // In the case of an S-line, we store the node name and the sequence length
-                let node_name: String = String::from(columns[1]);
-                let sequence_length: u64 = columns[2].trim().len() as u64;
-                seq_lengths.insert(node_name, sequence_length);
-            }
-            if first_char == 'P' {
-                let mut path_length: u64 = 0;
-                let path_name: String = String::from(columns[1]);
-
-                let node_list: Vec<String> = columns[2]
-                    .trim()
-                    .split(',')
-                    .map(|s| s.to_string())
-                    .collect();
-                for node_desc in node_list {
-                    let (node, orientation) = node_desc.split_at(node_desc.len() - 1);
-                    let sequence_length: &u64 = seq_lengths.get(node).unwrap();
-                    path_length += sequence_length;
-                    println!(
-                        "{}\t{}\t{}\t{}\t{}\t{}",
-                        node,
-                        path_name,
-                        path_length,
-                        path_length + sequence_length,
-                        sequence_length,
-                        orientation
-                    );
-                }
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        if let Some((path_name, offsets)) = path_offsets(&columns, &seq_lengths)? {
+            for step in offsets {
+                table.write_row(vec![
+                    Value::Text(step.node),
+                    Value::Text(path_name.clone()),
+                    Value::Integer(step.start as i64),
+                    Value::Integer(step.end as i64),
+                    Value::Integer((step.end - step.start) as i64),
+                    Value::Text(step.orientation.to_string()),
+                ])?;
             }
         }
         line.clear(); // Clear the line buffer for the next read
     }
 
-    Ok(())
+    table.finish()
 }
 
-pub fn lengths_gfa(file_path: &str) -> io::Result<()> {
+pub fn lengths_gfa(file_path: &str, format: OutputFormat) -> io::Result<()> {
     /*
-    This function reads a GFA file and prints the length of each path
+    This function reads a GFA file and prints the length of each segment
+    Segments without sequence ('*') take their length from their LN tag
      */
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
     let file: File = File::open(file_path)?;
     let mut reader: BufReader<File> = BufReader::new(file);
     let mut line: String = String::new();
+    let mut table: TableWriter = TableWriter::new(
+        format,
+        &[
+            ("NodeName", ColumnType::Text),
+            ("Length", ColumnType::Integer),
+        ],
+    )?;
 
     while reader.read_line(&mut line)? > 0 {
         let columns: Vec<&str> = line.split('\t').collect();
         if let Some(first_char) = line.chars().next() {
             if first_char == 'S' {
-                // In the case of an S-line, we store the node name and the sequence length
+                // In the case of an S-line, we print the node name and the sequence length
                 let node_name: String = String::from(columns[1]);
-                let sequence_length: u64 = columns[2].trim().len() as u64;
-                println!("{}\t{}", node_name, sequence_length);
+                let sequence_length: u64 = seq_lengths[columns[1]];
+                table.write_row(vec![
+                    Value::Text(node_name),
+                    Value::Integer(sequence_length as i64),
+                ])?;
             }
         }
         line.clear(); // Clear the line buffer for the next read
     }
 
-    Ok(())
+    table.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_file;
+
+    #[test]
+    fn test_path_offsets() {
+        let seq_lengths: HashMap<String, u64> =
+            HashMap::from([(String::from("1"), 4), (String::from("2"), 2)]);
+        let (path_name, steps) = path_offsets(&["P", "ref", "1+,2-,1+", "*"], &seq_lengths)
+            .unwrap()
+            .unwrap();
+        assert_eq!(path_name, "ref");
+        let offsets: Vec<(&str, u64, u64, char)> = steps
+            .iter()
+            .map(|step| (step.node.as_str(), step.start, step.end, step.orientation))
+            .collect();
+        assert_eq!(
+            offsets,
+            vec![("1", 0, 4, '+'), ("2", 4, 6, '-'), ("1", 6, 10, '+')]
+        );
+        // W-lines start at their start position, and are named after their PanSN name
+        let (path_name, steps) =
+            path_offsets(&["W", "HG1", "2", "chr1", "10", "16", "<2>1"], &seq_lengths)
+                .unwrap()
+                .unwrap();
+        assert_eq!(path_name, "HG1#2#chr1");
+        let offsets: Vec<(&str, u64, u64, char)> = steps
+            .iter()
+            .map(|step| (step.node.as_str(), step.start, step.end, step.orientation))
+            .collect();
+        assert_eq!(offsets, vec![("2", 10, 12, '-'), ("1", 12, 16, '+')]);
+        // P-lines start at the start of the range of their PanSN name
+        let (_, steps) = path_offsets(&["P", "HG1#2#chr1:10-16", "2-,1+", "*"], &seq_lengths)
+            .unwrap()
+            .unwrap();
+        assert_eq!((steps[0].start, steps[1].end), (10, 16));
+        assert!(path_offsets(&["S", "1", "ACGT"], &seq_lengths)
+            .unwrap()
+            .is_none());
+        assert!(path_offsets(&["P", "ref", "3+", "*"], &seq_lengths).is_err());
+    }
+
+    #[test]
+    fn test_rename_line() {
+        let regex: Regex = Regex::new(r"^(\w+)_(\w+)$").unwrap();
+        let rename = |name: &str| -> String {
+            match name {
+                "HG1#1#chr1" => String::from("HG1#2#chr1"),
+                "HG1#1#chr2" => String::from("HG1_chr2"),
+                _ => regex.replace(name, "$1#0#$2").to_string(),
+            }
+        };
+        let rename_segment = |segment: &str| -> String {
+            match segment {
+                "1" => String::from("s1"),
+                _ => segment.to_string(),
+            }
+        };
+        assert_eq!(
+            rename_line("P\tHG1_chr1\t1+,2-\t*\n", &rename, &rename_segment, false).unwrap(),
+            "P\tHG1#0#chr1\t1+,2-\t*"
+        );
+        assert_eq!(
+            rename_line("P\tref\t1+,2-\t*\n", &rename, &rename_segment, true).unwrap(),
+            "P\tref\ts1+,2-\t*"
+        );
+        assert_eq!(
+            rename_line(
+                "W\tHG1\t1\tchr1\t0\t5\t>1<2\n",
+                &rename,
+                &rename_segment,
+                true
+            )
+            .unwrap(),
+            "W\tHG1\t2\tchr1\t0\t5\t>s1<2"
+        );
+        // W-lines must keep a sample#haplotype#seqid name
+        assert!(rename_line(
+            "W\tHG1\t1\tchr2\t0\t5\t>1<2\n",
+            &rename,
+            &rename_segment,
+            false
+        )
+        .is_err());
+        assert_eq!(
+            rename_line("L\t1\t+\t2\t-\t0M\n", &rename, &rename_segment, true).unwrap(),
+            "L\ts1\t+\t2\t-\t0M"
+        );
+        assert_eq!(
+            rename_line("S\t1\tACGT\n", &rename, &rename_segment, false).unwrap(),
+            "S\t1\tACGT"
+        );
+    }
+
+    #[test]
+    fn test_rename_paths_errors() {
+        let file_path: String = temp_file("rename.gfa");
+        std::fs::write(&file_path, "S\t1\tACGT\nW\tHG1\t1\tchr1\t0\t4\t>1\n").unwrap();
+        let invalid_regex: io::Result<()> = rename_paths(&file_path, None, Some("("), "", None);
+        // The walk would lose its PanSN name, the graph is rejected before printing anything
+        let invalid_walk: io::Result<()> = rename_paths(&file_path, None, Some("#"), "_", None);
+        std::fs::remove_file(&file_path).unwrap();
+        assert_eq!(
+            invalid_regex.unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(invalid_walk.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_index_rows() {
+        let file_path: String = temp_file("test_index_rows.gfa");
+        std::fs::write(
+            &file_path,
+            "S\t1\tACGT\nS\t2\tNNGC\nS\t3\tGG\nS\t4\t*\tLN:i:2\n\
+             P\tref\t1+,2+,3+\t*\nP\trev\t3-,2-,1-\t*\n\
+             W\tHG1\t1\tchr1\t0\t10\t>1>3>4>4\n",
+        )
+        .unwrap();
+        let rows: Vec<Vec<Value>> = index_rows(&file_path, Some("ref")).unwrap();
+        let no_reference: Vec<Vec<Value>> = index_rows(&file_path, None).unwrap();
+        let unknown_reference: io::Result<Vec<Vec<Value>>> = index_rows(&file_path, Some("chr2"));
+        std::fs::remove_file(&file_path).unwrap();
+
+        assert_eq!(
+            rows[0],
+            vec![
+                Value::Text(String::from("ref")),
+                Value::Text(String::from(".")),
+                Value::Text(String::from(".")),
+                Value::Integer(10),
+                Value::Integer(10),
+                Value::Integer(0),
+                Value::Integer(3),
+                Value::Integer(3),
+                Value::Integer(0),
+                Value::Float(0.2),
+                Value::Float(0.75),
+                Value::Integer(0),
+                Value::Integer(0),
+            ]
+        );
+        // The reverse strand of the reference has no breakpoint
+        assert_eq!(rows[1][4..6], [Value::Integer(0), Value::Integer(10)]);
+        assert_eq!(rows[1][11], Value::Integer(0));
+        // Segment 4 is private to the W-line, and only counted once although visited twice;
+        // its length comes from its LN tag
+        assert_eq!(
+            rows[2],
+            vec![
+                Value::Text(String::from("HG1#1#chr1")),
+                Value::Text(String::from("HG1")),
+                Value::Text(String::from("1")),
+                Value::Integer(10),
+                Value::Integer(10),
+                Value::Integer(0),
+                Value::Integer(4),
+                Value::Integer(3),
+                Value::Integer(1),
+                Value::Float(0.0),
+                Value::Float(0.4),
+                Value::Integer(3),
+                Value::Integer(2),
+            ]
+        );
+        assert_eq!(no_reference[2][11], Value::Missing);
+        assert!(unknown_reference.is_err());
+    }
 }
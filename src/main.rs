@@ -1,15 +1,32 @@
 mod anchor;
+mod annotate;
+mod bubbles;
+mod compact;
+mod components;
 mod concatenate;
 mod converter;
+mod dot;
+mod find;
+mod gaf;
 mod index_gfa_file;
 mod mask_paths;
 mod optimize;
 mod reconstruct;
+mod remap;
 mod remove_loops;
+mod sequence;
 mod sharepg;
 mod simplify_graph;
 mod spurious;
+mod stats;
+mod table;
+#[cfg(test)]
+mod test_utils;
+mod validate;
+mod vcf;
+mod walks;
 use clap::{Parser,Subcommand};
+use std::process::exit;
 
 #[derive(Parser, Debug)]
 #[command(version = "v0.1.0", about, long_about = None)]
@@ -23,11 +40,20 @@ struct Cli {
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// Rename the paths to the names given in the file.
+    /// Rename the paths to the names given in the file, or following a pattern.
     Rename {
         /// Path to tab-separated file with old names and new names for paths
         #[arg(short = 'r', long = "rename")]
-        rename_file: String,
+        rename_file: Option<String>,
+        /// Regex applied to path names (W-lines are matched as sample#haplotype#seqid)
+        #[arg(short = 'p', long = "pattern")]
+        pattern: Option<String>,
+        /// Replacement for the names matched by the pattern, e.g. '$1#$2#$3'
+        #[arg(short = 't', long = "template", default_value = "", requires = "pattern")]
+        template: String,
+        /// Path to tab-separated file with old and new IDs to rename segments
+        #[arg(short = 's', long = "segments")]
+        segments_file: Option<String>,
     },
     /// Computes shared parts of a pangenome
     Share {
@@ -40,29 +66,56 @@ enum Commands {
         /// Sensitivity ratio for the query intervals (from 0.0 to 1.0)
         #[arg(short = 's', long = "sensitivity", default_value_t = 1.0)]
         sensitivity: f64,
+        /// Output format
+        #[arg(short = 'f', long = "format", value_enum, default_value = "tsv")]
+        format: table::OutputFormat,
     },
     /// Convert to rGFA using the reference as a backbone for the offset tree.
     Convert {
         /// Name of reference path in graph, used to construct rgfa tree
         #[arg(short = 'R', long = "rgfa")]
         rgfa_reference: String,
+        /// Paths to process first, in this order, when assigning segments and ranks
+        #[arg(short = 'o', long = "order")]
+        order: Vec<String>,
+    },
+    /// Convert a rGFA back to GFA, with a path for each stable sequence
+    FromRgfa {
+        /// Write stable sequences as W-lines instead of P-lines
+        #[arg(short = 'w', long = "walks")]
+        walks: bool,
     },
     /// Search for anchor nodes
     Anchors {
         /// Minimum number of crossing distinct haplotypes to consider a node as an anchor
         #[arg(short = 'a', long = "anchor")]
         anchor: Option<i32>,
+        /// Output format
+        #[arg(short = 'f', long = "format", value_enum, default_value = "tsv")]
+        format: table::OutputFormat,
     },
     /// Computes offsets of the nodes in the graph
-    Offsets {},
+    Offsets {
+        /// Output format
+        #[arg(short = 'f', long = "format", value_enum, default_value = "tsv")]
+        format: table::OutputFormat,
+    },
     /// Computes a simplified version of the graph
     Simplify {},
     /// Computes spurious breakpoints in the graph
     Spurious {},
     /// Computes lengths of the nodes in the graph
-    Lengths {},
+    Lengths {
+        /// Output format
+        #[arg(short = 'f', long = "format", value_enum, default_value = "tsv")]
+        format: table::OutputFormat,
+    },
     /// Reconstruct paths from the graph
-    Reconstruct {},
+    Reconstruct {
+        /// Read sequences back from the file instead of storing them in memory
+        #[arg(short = 'm', long = "low-memory")]
+        low_memory: bool,
+    },
     /// Computes a simplified version of the graph
     Loops {},
     /// Filter the paths to be removed from the graph
@@ -70,21 +123,180 @@ enum Commands {
         /// Paths names to be removed
         #[clap(long = "mask", short = 'M', action)]
         mask: Vec<String>,
+        /// File with one path name to be removed per line
+        #[arg(short = 'F', long = "paths-file")]
+        paths_file: Option<String>,
+        /// Regex selecting paths by name
+        #[arg(short = 'r', long = "regex")]
+        patterns: Vec<String>,
+        /// PanSN sample whose paths are selected
+        #[arg(short = 'S', long = "sample")]
+        samples: Vec<String>,
+        /// Keep only the selected paths instead of removing them
+        #[arg(short = 'k', long = "keep")]
+        keep: bool,
+        /// Merge chains of segments left by the removal, and drop tips
+        #[arg(short = 'c', long = "compact")]
+        compact: bool,
+        /// Drop links that no remaining path traverses
+        #[arg(short = 'u', long = "drop-unused-links")]
+        drop_unused_links: bool,
     },
     /// Optimize the graph, reallocating IDs of the nodes
     Opitmize {
         /// Location to store mapping between old and new series of node IDs
         #[arg(short = 'O', long = "optimize")]
         output_mapping: String,
+        /// Order in which nodes receive their new IDs
+        #[arg(short = 's', long = "sort", value_enum, default_value = "file")]
+        ordering: optimize::Ordering,
+        /// Path to start from, for reference and bfs orderings
+        #[arg(short = 'R', long = "reference")]
+        reference: Option<String>,
+    },
+    /// Translate node IDs of a GFA, GAF or TSV file through a mapping file
+    Remap {
+        /// Mapping between old and new node IDs, as written by Opitmize or Concatenate
+        #[arg(short = 'm', long = "mapping")]
+        mapping_file: String,
+        /// Translate new IDs back to old ones
+        #[arg(short = 'i', long = "invert")]
+        invert: bool,
+        /// Format of the file, guessed from its extension if not given
+        #[arg(short = 'f', long = "format", value_enum)]
+        format: Option<remap::Format>,
+        /// Columns (1-based) holding node IDs in a TSV file
+        #[arg(short = 'c', long = "columns", default_value = "1")]
+        columns: Vec<usize>,
+        /// Graph whose rows are used, required for mappings written by Concatenate
+        #[arg(short = 'g', long = "graph")]
+        graph: Option<String>,
     },
-    /// Concatenate graph with a second one, keeping tags.
+    /// Concatenate graph with other ones, keeping tags.
     Concatenate {
-        /// Path to second GFA to concatenate with the first one
-        #[arg(short = 'c', long = "concat")]
-        graph_to_concat: String,
+        /// Path to a GFA to concatenate with the first one (can be given several times)
+        #[arg(short = 'c', long = "concat", required = true)]
+        graphs_to_concat: Vec<String>,
+        /// Location to store mapping between old and new node IDs of each graph
+        #[arg(short = 'O', long = "output-mapping")]
+        output_mapping: Option<String>,
+        /// Unify segments with identical sequence and neighborhood across graphs
+        #[arg(short = 'm', long = "merge")]
+        merge: bool,
     },
     /// Retrieve basic information about the paths of the graph
-    Index {},
+    Index {
+        /// Path to count breakpoints against
+        #[arg(short = 'R', long = "reference")]
+        reference: Option<String>,
+        /// Output format
+        #[arg(short = 'f', long = "format", value_enum, default_value = "tsv")]
+        format: table::OutputFormat,
+    },
+    /// Convert PanSN-named P-lines to W-lines, or W-lines back to P-lines
+    Walks {
+        /// Convert W-lines to P-lines instead
+        #[arg(short = 'p', long = "to-paths")]
+        to_paths: bool,
+    },
+    /// Find superbubbles in the graph, with their nesting and the traversals taken by paths
+    Bubbles {
+        /// Location to store the traversals of each bubble and the paths that take them
+        #[arg(short = 't', long = "traversals")]
+        traversals_file: Option<String>,
+    },
+    /// Check the consistency of the graph, reporting every problem found
+    Validate {},
+    /// Summarize the graph: counts, lengths, degrees, components and coverage by paths
+    Stats {
+        /// Print the summary as a JSON object
+        #[arg(short = 'j', long = "json")]
+        json: bool,
+    },
+    /// Compute connected components from links and paths, optionally writing each one to a GFA
+    Components {
+        /// Sample whose contigs are used to name the components
+        #[arg(short = 'R', long = "reference")]
+        reference_sample: Option<String>,
+        /// Directory where each component is written, named after its dominant contig
+        #[arg(short = 'd', long = "output-dir")]
+        output_dir: Option<String>,
+        /// Only write the components containing these paths
+        #[arg(short = 'p', long = "paths", requires = "output_dir")]
+        paths: Vec<String>,
+    },
+    /// Compute the coverage of segments and paths from a GAF file of alignments to the graph
+    Coverage {
+        /// GAF file (plain or gzip) of alignments to the graph
+        #[arg(short = 'a', long = "alignments")]
+        alignments: String,
+        /// Location to store the coverage projected on the steps of each path
+        #[arg(short = 't', long = "tracks")]
+        tracks_file: Option<String>,
+    },
+    /// Find exact matches of DNA sequences along the graph, and the paths containing them
+    Find {
+        /// Query sequence (can be repeated)
+        #[arg(short = 'q', long = "query")]
+        queries: Vec<String>,
+        /// FASTA file of query sequences
+        #[arg(short = 'Q', long = "fasta")]
+        fasta_file: Option<String>,
+        /// Output format
+        #[arg(short = 'f', long = "format", value_enum, default_value = "tsv")]
+        format: table::OutputFormat,
+    },
+    /// Export the graph, or a region of it, in the DOT format of Graphviz
+    Dot {
+        /// Range of segment IDs to export (first-last), or a single segment ID
+        #[arg(short = 'n', long = "nodes")]
+        nodes: Option<String>,
+        /// Interval of a path to export (path:start-end)
+        #[arg(short = 'i', long = "interval")]
+        interval: Option<String>,
+        /// Number of links to follow around the exported region
+        #[arg(short = 'c', long = "context", default_value_t = 0)]
+        context: usize,
+        /// Paths to draw over the graph (all if not given)
+        #[arg(short = 'p', long = "paths")]
+        paths: Vec<String>,
+    },
+    /// Project a GFF3 or BED annotation of a path on the segments, and report its presence in other paths
+    Annotate {
+        /// GFF3 file, or BED file if its extension is .bed, annotating the reference
+        #[arg(short = 'a', long = "annotation")]
+        annotation: String,
+        /// Name of the annotated path
+        #[arg(short = 'R', long = "reference")]
+        reference: String,
+        /// GFF3 feature types to project (all if not given)
+        #[arg(short = 't', long = "types")]
+        types: Vec<String>,
+        /// Location to store the interval of each segment covered by each feature
+        #[arg(short = 's', long = "segments")]
+        segments_file: Option<String>,
+        /// Location to store the presence of each feature in each path
+        #[arg(short = 'p', long = "presence")]
+        presence_file: Option<String>,
+    },
+    /// Project GAF alignments on the linear coordinates of a path, as BED or SAM
+    Surject {
+        /// GAF file (plain or gzip) of alignments to the graph
+        #[arg(short = 'a', long = "alignments")]
+        alignments: String,
+        /// Name of the path giving linear coordinates
+        #[arg(short = 'R', long = "reference")]
+        reference: String,
+        /// Output format
+        #[arg(short = 'f', long = "format", value_enum, default_value = "bed")]
+        format: gaf::ProjectionFormat,
+    },
+    /// Export the bubbles crossed by a reference path as a VCF, with genotypes of each sample
+    Vcf {
+        /// Name of the path used as reference for positions and REF alleles
+        #[arg(short = 'R', long = "reference")]
+        reference: String,
+    },
 }
 
 fn main() {
@@ -105,63 +317,238 @@ fn main() {
     let args: Cli = Cli::parse();
 
     match &args.cmd {
-        Commands::Rename { rename_file } => {
-            let _ = index_gfa_file::rename_paths(&args.file_path, &rename_file);
+        Commands::Rename { rename_file, pattern, template, segments_file } => {
+            if let Err(e) = index_gfa_file::rename_paths(
+                &args.file_path,
+                rename_file.as_deref(),
+                pattern.as_deref(),
+                template,
+                segments_file.as_deref(),
+            ) {
+                eprintln!("Error renaming paths: {}", e);
+                exit(1);
+            }
         }
-        Commands::Share { include, exclude, sensitivity } => {
-            let _ = sharepg::shared_nodes(
+        Commands::Share { include, exclude, sensitivity, format } => {
+            if let Err(e) = sharepg::shared_nodes(
                 &args.file_path,
-                &include,
-                &exclude,
+                include,
+                exclude,
                 *sensitivity,
-            );
+                *format,
+            ) {
+                eprintln!("Error computing shared nodes: {}", e);
+                exit(1);
+            }
+        }
+        Commands::Convert { rgfa_reference, order } => {
+            if let Err(e) = converter::gfa_to_rgfa(&args.file_path, rgfa_reference, order) {
+                eprintln!("Error converting to rGFA: {}", e);
+                exit(1);
+            }
         }
-        Commands::Convert { rgfa_reference } => {
-            let _ = converter::gfa_to_rgfa(&args.file_path, &rgfa_reference);
+        Commands::FromRgfa { walks } => {
+            if let Err(e) = converter::rgfa_to_gfa(&args.file_path, *walks) {
+                eprintln!("Error converting from rGFA: {}", e);
+                exit(1);
+            }
         }
-        Commands::Anchors { anchor } => {
-            let _ = anchor::anchor_nodes(&args.file_path, *anchor);
+        Commands::Anchors { anchor, format } => {
+            if let Err(e) = anchor::anchor_nodes(&args.file_path, *anchor, *format) {
+                eprintln!("Error computing anchors: {}", e);
+                exit(1);
+            }
         }
-        Commands::Offsets { } => {
-            let _ = index_gfa_file::offset_gfa(&args.file_path);
+        Commands::Offsets { format } => {
+            if let Err(e) = index_gfa_file::offset_gfa(&args.file_path, *format) {
+                eprintln!("Error computing offsets: {}", e);
+                exit(1);
+            }
 
         }
         Commands::Simplify { } => {
-            let _ = simplify_graph::simplify_graph(&args.file_path);
+            simplify_graph::simplify_graph(&args.file_path);
 
         }
         Commands::Spurious { } => {
-            let _ = spurious::prune_spurious_breakpoints(&args.file_path);
+            if let Err(e) = spurious::prune_spurious_breakpoints(&args.file_path) {
+                eprintln!("Error pruning spurious breakpoints: {}", e);
+                exit(1);
+            }
 
         }
-        Commands::Lengths { } => {
-            let _ = index_gfa_file::lengths_gfa(&args.file_path);
+        Commands::Lengths { format } => {
+            if let Err(e) = index_gfa_file::lengths_gfa(&args.file_path, *format) {
+                eprintln!("Error computing lengths: {}", e);
+                exit(1);
+            }
 
         }
-        Commands::Reconstruct { } => {
-            let _ = reconstruct::reconstruct_paths(&args.file_path);
+        Commands::Reconstruct { low_memory } => {
+            if let Err(e) = reconstruct::reconstruct_paths(&args.file_path, *low_memory) {
+                eprintln!("Error reconstructing paths: {}", e);
+                exit(1);
+            }
 
         }
         Commands::Loops { } => {
-            let _ = remove_loops::remove_loops(&args.file_path, 2);
+            remove_loops::remove_loops(&args.file_path, 2);
 
         }
-        Commands::Mask { mask } => {
-            let _ = mask_paths::mask_paths(
+        Commands::Mask { mask, paths_file, patterns, samples, keep, compact, drop_unused_links } => {
+            let mut names: Vec<String> = mask.clone();
+            if let Some(paths_file) = paths_file {
+                match mask_paths::read_path_list(paths_file) {
+                    Ok(path_names) => names.extend(path_names),
+                    Err(e) => {
+                        eprintln!("Error reading {}: {}", paths_file, e);
+                        exit(1);
+                    }
+                }
+            }
+            if let Err(e) = mask_paths::mask_paths(
                 &args.file_path,
-                mask.iter().map(String::as_str).collect(),
-            );
+                &names,
+                patterns,
+                samples,
+                *keep,
+                *compact,
+                *drop_unused_links,
+            ) {
+                eprintln!("Error masking paths: {}", e);
+                exit(1);
+            }
         }
-        Commands::Opitmize { output_mapping } => {
-            let _ = optimize::relocate_ids(&args.file_path, output_mapping);
+        Commands::Opitmize { output_mapping, ordering, reference } => {
+            if let Err(e) = optimize::relocate_ids(
+                &args.file_path,
+                output_mapping,
+                *ordering,
+                reference.as_deref(),
+            ) {
+                eprintln!("Error renumbering segments: {}", e);
+                exit(1);
+            }
 
         }
-        Commands::Concatenate { graph_to_concat } => {
-            let _ = concatenate::concat_graphs(&args.file_path, graph_to_concat);
+        Commands::Remap { mapping_file, invert, format, columns, graph } => {
+            if let Err(e) = remap::remap_ids(
+                &args.file_path,
+                mapping_file,
+                *invert,
+                *format,
+                columns,
+                graph.as_deref(),
+            ) {
+                eprintln!("Error remapping IDs: {}", e);
+                exit(1);
+            }
+        }
+        Commands::Concatenate { graphs_to_concat, output_mapping, merge } => {
+            if let Err(e) = concatenate::concat_graphs(
+                &args.file_path,
+                graphs_to_concat,
+                output_mapping.as_deref(),
+                *merge,
+            ) {
+                eprintln!("Error concatenating graphs: {}", e);
+                exit(1);
+            }
 
         }
-        Commands::Index {  } => {
-            let _ = index_gfa_file::index_gfa(&args.file_path);
+        Commands::Index { reference, format } => {
+            if let Err(e) = index_gfa_file::index_gfa(&args.file_path, reference.as_deref(), *format) {
+                eprintln!("Error indexing paths: {}", e);
+                exit(1);
+            }
+        }
+        Commands::Walks { to_paths } => {
+            if let Err(e) = walks::convert_paths(&args.file_path, *to_paths) {
+                eprintln!("Error converting paths: {}", e);
+                exit(1);
+            }
+        }
+        Commands::Bubbles { traversals_file } => {
+            if let Err(e) = bubbles::bubbles_gfa(&args.file_path, traversals_file.as_deref()) {
+                eprintln!("Error finding bubbles: {}", e);
+                exit(1);
+            }
+        }
+        Commands::Validate {} => {
+            match validate::validate_gfa(&args.file_path) {
+                Ok(0) => (),
+                Ok(_) => exit(1),
+                Err(e) => {
+                    eprintln!("Error reading GFA: {}", e);
+                    exit(2);
+                }
+            }
+        }
+        Commands::Stats { json } => {
+            if let Err(e) = stats::stats_gfa(&args.file_path, *json) {
+                eprintln!("Error computing statistics: {}", e);
+                exit(1);
+            }
+        }
+        Commands::Components { reference_sample, output_dir, paths } => {
+            if let Err(e) = components::split_components(
+                &args.file_path,
+                reference_sample.as_deref(),
+                output_dir.as_deref(),
+                paths,
+            ) {
+                eprintln!("Error splitting components: {}", e);
+                exit(1);
+            }
+        }
+        Commands::Coverage { alignments, tracks_file } => {
+            if let Err(e) = gaf::coverage_gaf(&args.file_path, alignments, tracks_file.as_deref()) {
+                eprintln!("Error computing coverage: {}", e);
+                exit(1);
+            }
+        }
+        Commands::Find { queries, fasta_file, format } => {
+            if let Err(e) = find::find_sequences(&args.file_path, queries, fasta_file.as_deref(), *format) {
+                eprintln!("Error finding sequences: {}", e);
+                exit(1);
+            }
+        }
+        Commands::Dot { nodes, interval, context, paths } => {
+            if let Err(e) = dot::export_dot(
+                &args.file_path,
+                nodes.as_deref(),
+                interval.as_deref(),
+                *context,
+                paths,
+            ) {
+                eprintln!("Error exporting DOT: {}", e);
+                exit(1);
+            }
+        }
+        Commands::Annotate { annotation, reference, types, segments_file, presence_file } => {
+            if let Err(e) = annotate::annotate_gfa(
+                &args.file_path,
+                annotation,
+                reference,
+                types,
+                segments_file.as_deref(),
+                presence_file.as_deref(),
+            ) {
+                eprintln!("Error annotating graph: {}", e);
+                exit(1);
+            }
+        }
+        Commands::Surject { alignments, reference, format } => {
+            if let Err(e) = gaf::surject_gaf(&args.file_path, alignments, reference, *format) {
+                eprintln!("Error surjecting alignments: {}", e);
+                exit(1);
+            }
+        }
+        Commands::Vcf { reference } => {
+            if let Err(e) = vcf::vcf_gfa(&args.file_path, reference) {
+                eprintln!("Error writing VCF: {}", e);
+                exit(1);
+            }
         }
     }
 
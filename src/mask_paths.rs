@@ -1,6 +1,9 @@
+use crate::compact::compact_lines;
+use crate::walks::{read_lengths, read_next_p_step, read_next_w_step, split_pansn};
+use regex::Regex;
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
 
 fn index_gfa(
     file_path: &str,
@@ -22,7 +25,6 @@ fn index_gfa(
         let columns: Vec<&str> = line.split('\t').collect();
         if let Some(first_char) = line.chars().next() {
             if first_char == 'W' {
-                path_types.insert(columns[1].to_string(), 'W');
                 // In the case of a W-line, we store the path name and the offset of the path description
                 // When processing paths, we can match paths in the path_positions HashMap
                 // Then start reading the file from there and go with a buffer to read node by node the path
@@ -35,7 +37,7 @@ fn index_gfa(
                         .trim_end_matches("#0")
                         .to_string()
                 };
-                let offset: u64 = reader.seek(io::SeekFrom::Current(0))?
+                let offset: u64 = reader.stream_position()?
                     - (line.len() as u64
                         - columns[0].len() as u64
                         - columns[1].len() as u64
@@ -44,9 +46,9 @@ fn index_gfa(
                         - columns[4].len() as u64
                         - columns[5].len() as u64
                         - 6);
-                path_positions.insert(path_name.clone(), offset + 1);
+                path_types.insert(path_name.clone(), 'W');
+                path_positions.insert(path_name.clone(), offset);
             } else if first_char == 'P' {
-                path_types.insert(columns[1].to_string(), 'P');
                 // In the case of a P-line, we store the path name and the offset of the path description
                 // When processing paths, we can match paths in the path_positions HashMap
                 // Then start reading the file from there and go with a buffer to read node by node the path
@@ -59,8 +61,9 @@ fn index_gfa(
                         .trim_end_matches("#0")
                         .to_string()
                 };
-                let offset: u64 = reader.seek(io::SeekFrom::Current(0))?
+                let offset: u64 = reader.stream_position()?
                     - (line.len() as u64 - columns[0].len() as u64 - columns[1].len() as u64 - 2);
+                path_types.insert(path_name.clone(), 'P');
                 path_positions.insert(path_name.clone(), offset);
             }
         }
@@ -70,10 +73,154 @@ fn index_gfa(
     Ok((path_positions, path_types))
 }
 
-pub fn mask_paths(file_path: &str, selected_paths: Vec<&str>) -> io::Result<()> {
+pub fn read_path_list(list_file: &str) -> io::Result<Vec<String>> {
+    /*
+    Reads a file with one path name per line (extra tab-separated columns are ignored)
+     */
+    let file: File = File::open(list_file)?;
+    let reader: BufReader<File> = BufReader::new(file);
+    let mut path_names: Vec<String> = Vec::new();
+    for line in reader.lines() {
+        let line: String = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        path_names.push(
+            line.split('\t')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+        );
+    }
+    Ok(path_names)
+}
+
+fn select_paths(
+    path_positions: &HashMap<String, u64>,
+    names: &[String],
+    patterns: &[String],
+    samples: &[String],
+) -> io::Result<HashSet<String>> {
+    /*
+    Gathers the paths of the graph selected by name, by regex on their name, or by PanSN sample
+    W-lines are named sample#haplotype#contig
+     */
+    let mut selected: HashSet<String> = HashSet::new();
+    for name in names.iter() {
+        if !path_positions.contains_key(name) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Path not found in graph: {}", name),
+            ));
+        }
+        selected.insert(name.clone());
+    }
+    for pattern in patterns.iter() {
+        let regex: Regex = Regex::new(pattern)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        selected.extend(
+            path_positions
+                .keys()
+                .filter(|path_name| regex.is_match(path_name))
+                .cloned(),
+        );
+    }
+    selected.extend(
+        path_positions
+            .keys()
+            .filter(|path_name| {
+                split_pansn(path_name)
+                    .is_some_and(|pansn| samples.iter().any(|s| s == pansn.sample))
+            })
+            .cloned(),
+    );
+    Ok(selected)
+}
+
+// An oriented link between two segments, read in the direction giving the smallest tuple
+type Link = (u32, bool, u32, bool);
+
+fn canonical_link(from: (u32, bool), to: (u32, bool)) -> Link {
+    (from.0, from.1, to.0, to.1).min((to.0, !to.1, from.0, !from.1))
+}
+
+fn read_path_steps(
+    gfa: &mut BufReader<File>,
+    position: u64,
+    path_type: Option<&char>,
+) -> io::Result<Vec<(u32, bool)>> {
+    /*
+    Reads the steps of a path from the offset of its description, as node IDs and forward orientation
+     */
+    gfa.seek(SeekFrom::Start(position))?;
+    let mut steps: Vec<(u32, bool)> = Vec::new();
+    loop {
+        let step: Option<(String, char)> = match path_type {
+            Some('P') => read_next_p_step(gfa)?,
+            Some('W') => read_next_w_step(gfa)?,
+            _ => None,
+        };
+        let Some((node, orientation)) = step else {
+            break;
+        };
+        if let Ok(node) = node.parse::<u32>() {
+            steps.push((node, orientation == '+' || orientation == '>'));
+        }
+    }
+    Ok(steps)
+}
+
+pub fn mask_paths(
+    file_path: &str,
+    names: &[String],
+    patterns: &[String],
+    samples: &[String],
+    keep: bool,
+    compact: bool,
+    drop_unused_links: bool,
+) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    let report: Vec<(String, usize, u64, usize)> = write_masked(
+        file_path,
+        names,
+        patterns,
+        samples,
+        keep,
+        compact,
+        drop_unused_links,
+        &mut writer,
+    )?;
+    writer.flush()?;
+
+    eprintln!("# MaskedPath\tRemovedSegments\tRemovedLength\tRemovedLinks");
+    for (path_name, segments, length, links) in report {
+        eprintln!("{}\t{}\t{}\t{}", path_name, segments, length, links);
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_masked<W: Write>(
+    file_path: &str,
+    names: &[String],
+    patterns: &[String],
+    samples: &[String],
+    keep: bool,
+    compact: bool,
+    drop_unused_links: bool,
+    writer: &mut W,
+) -> io::Result<Vec<(String, usize, u64, usize)>> {
     /*
+    This function reads a GFA file and removes the selected paths, with the segments and links only they traverse
+    Paths are selected by name, by regex on their name, or by PanSN sample
+    - keep: keep only the selected paths instead, dropping all segments and links they do not traverse
+      (links between kept segments included, as with drop_unused_links)
+    - compact: merge chains left by the removal and drop tips, see compact::compact_lines
+    - drop_unused_links: also drop links between kept segments that no remaining path traverses
+    Returns, for each masked path, the number of segments, bp and links removed along it
      */
-    eprintln!("Removing paths {:?} from {}", selected_paths, file_path);
 
     // STEP 1: extract path information from the graph
     let (mut path_positions, path_types) = match index_gfa(file_path, true) {
@@ -83,130 +230,244 @@ pub fn mask_paths(file_path: &str, selected_paths: Vec<&str>) -> io::Result<()>
             return Err(e);
         }
     };
-    // STEP 2: gather nodes present in selected paths
+    let selected_paths: HashSet<String> = select_paths(&path_positions, names, patterns, samples)?;
+    // Keeping no path would drop the whole graph
+    if keep && selected_paths.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "No path of the graph is selected to be kept",
+        ));
+    }
+    // Paths to be removed are the selected ones, or all the others when keeping them
+    let mut removed_paths: Vec<String> = path_positions
+        .keys()
+        .filter(|path_name| selected_paths.contains(*path_name) != keep)
+        .cloned()
+        .collect();
+    removed_paths.sort();
+    eprintln!("Removing paths {:?} from {}", removed_paths, file_path);
+
+    // STEP 2: gather nodes present in removed paths
     let mut gfa: BufReader<File> = BufReader::new(File::open(file_path)?);
 
     let mut node_arena: HashSet<u32> = HashSet::new();
+    // Segments and links of each removed path, for the report
+    let mut removed_elements: Vec<(HashSet<u32>, HashSet<Link>)> = Vec::new();
     // We read each path
-    for path_name in selected_paths {
-        // Reading the file
-        gfa.seek(SeekFrom::Start(*path_positions.get(path_name).ok_or_else(
-            || {
-                io::Error::new(
-                    io::ErrorKind::NotFound,
-                    format!("Path not found in graph: {}", path_name),
-                )
-            },
-        )?))?;
-        let mut buffer: [u8; 1] = [0; 1];
-        // Reading path contents
-        if path_types.get(path_name) == Some(&'P') {
-            while let Ok(node) = read_next_p_node(&mut gfa, &mut buffer).parse::<u32>() {
-                node_arena.insert(node);
-            }
-        } else if path_types.get(path_name) == Some(&'W') {
-            while let Ok(node) = read_next_w_node(&mut gfa, &mut buffer).parse::<u32>() {
-                node_arena.insert(node);
-            }
-        }
-        // We remove the selected_paths from the offsets hashmaps
+    for path_name in removed_paths.iter() {
+        let steps: Vec<(u32, bool)> = read_path_steps(
+            &mut gfa,
+            path_positions[path_name],
+            path_types.get(path_name),
+        )?;
+        node_arena.extend(steps.iter().map(|(node, _)| *node));
+        removed_elements.push((
+            steps.iter().map(|(node, _)| *node).collect(),
+            steps
+                .windows(2)
+                .map(|window| canonical_link(window[0], window[1]))
+                .collect(),
+        ));
+        // We remove the removed paths from the offsets hashmaps
         path_positions.remove(path_name);
     }
     // STEP 3: remove from the HashSet nodes that are present in other paths
-    for r_path_name in path_positions.keys() {
-        // Reading the file
-        gfa.seek(SeekFrom::Start(
-            *path_positions.get(r_path_name).ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::NotFound,
-                    format!("Path not found in graph: {}", r_path_name),
-                )
-            })?,
-        ))?;
-        let mut buffer: [u8; 1] = [0; 1];
-        // Reading path contents
-        if path_types.get(r_path_name) == Some(&'P') {
-            while let Ok(node) = read_next_p_node(&mut gfa, &mut buffer).parse::<u32>() {
-                node_arena.remove(&node);
-            }
-        } else if path_types.get(r_path_name) == Some(&'W') {
-            while let Ok(node) = read_next_w_node(&mut gfa, &mut buffer).parse::<u32>() {
-                node_arena.remove(&node);
-            }
+    let mut kept_nodes: HashSet<u32> = HashSet::new();
+    let mut kept_links: HashSet<Link> = HashSet::new();
+    for (r_path_name, position) in path_positions.iter() {
+        let steps: Vec<(u32, bool)> =
+            read_path_steps(&mut gfa, *position, path_types.get(r_path_name))?;
+        for (node, _) in steps.iter() {
+            node_arena.remove(node);
+            kept_nodes.insert(*node);
         }
+        kept_links.extend(
+            steps
+                .windows(2)
+                .map(|window| canonical_link(window[0], window[1])),
+        );
     }
+    // When keeping paths, segments traversed by no path are dropped as well
+    let is_dropped = |node: &u32| -> bool {
+        if keep {
+            !kept_nodes.contains(node)
+        } else {
+            node_arena.contains(node)
+        }
+    };
     // STEP 4: filter graph structures
     let file: File = File::open(file_path)?;
     let mut reader: BufReader<File> = BufReader::new(file);
     let mut line: String = String::new();
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
+    let mut dropped_lengths: HashMap<u32, u64> = HashMap::new();
+    let mut dropped_links: HashSet<Link> = HashSet::new();
+    // When compacting, kept lines are gathered to be rewritten
+    let mut kept_lines: Vec<String> = Vec::new();
+    let mut print = |line: &str| -> io::Result<()> {
+        if compact {
+            kept_lines.push(line.trim_end_matches(['\n', '\r']).to_string());
+            Ok(())
+        } else {
+            write!(writer, "{}", line)
+        }
+    };
     while reader.read_line(&mut line)? > 0 {
         let columns: Vec<&str> = line.split('\t').collect();
         if let Some(first_char) = line.chars().next() {
             if first_char == 'S' {
                 if let Ok(node_id) = columns[1].parse::<u32>() {
-                    if !node_arena.contains(&node_id) {
-                        print!("{}", line)
+                    if !is_dropped(&node_id) {
+                        print(&line)?
+                    } else {
+                        dropped_lengths.insert(node_id, seq_lengths[columns[1]]);
                     }
                 }
             } else if first_char == 'L' {
                 if let (Ok(node_id_from), Ok(node_id_to)) =
                     (columns[1].parse::<u32>(), columns[3].parse::<u32>())
                 {
-                    if !node_arena.contains(&node_id_from) && !node_arena.contains(&node_id_to) {
-                        print!("{}", line)
+                    let link: Link = canonical_link(
+                        (node_id_from, columns[2] == "+"),
+                        (node_id_to, columns[4] == "+"),
+                    );
+                    if !is_dropped(&node_id_from)
+                        && !is_dropped(&node_id_to)
+                        && (!(keep || drop_unused_links) || kept_links.contains(&link))
+                    {
+                        print(&line)?
+                    } else {
+                        dropped_links.insert(link);
                     }
                 }
             } else if first_char == 'W' {
                 if path_positions
                     .contains_key(&(columns[1].to_string() + "#" + columns[2] + "#" + columns[3]))
                 {
-                    print!("{}", line)
+                    print(&line)?
                 }
             } else if first_char == 'P' {
                 if path_positions.contains_key(columns[1]) {
-                    print!("{}", line)
+                    print(&line)?
                 }
             } else {
-                print!("{}", line)
+                print(&line)?
             }
         }
         line.clear(); // Clear the line buffer for the next read
     }
+    if compact {
+        for line in compact_lines(kept_lines)? {
+            writeln!(writer, "{}", line)?;
+        }
+    }
 
-    Ok(())
+    let mut report: Vec<(String, usize, u64, usize)> = Vec::new();
+    for (path_name, (segments, links)) in removed_paths.into_iter().zip(removed_elements.iter()) {
+        let removed_segments: Vec<u64> = segments
+            .iter()
+            .filter_map(|node| dropped_lengths.get(node).copied())
+            .collect();
+        report.push((
+            path_name,
+            removed_segments.len(),
+            removed_segments.iter().sum::<u64>(),
+            links.intersection(&dropped_links).count(),
+        ));
+    }
+
+    Ok(report)
 }
 
-fn read_next_p_node(file: &mut BufReader<File>, buffer: &mut [u8; 1]) -> String {
-    /*
-     * Read the next node in the file, until a comma is found
-     * file: the file to read
-     * buffer: a buffer to read the file
-     */
-    let mut node: String = String::new();
-    while file.read(buffer).unwrap() > 0 {
-        if buffer[0] == b'+' || buffer[0] == b'-' {
-            break;
-        }
-        if buffer[0] != b',' {
-            node.push(buffer[0] as char);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_file;
+
+    #[test]
+    fn test_keep_drops_untraversed_links() {
+        let file_path: String = temp_file("test_mask_keep.gfa");
+        // The link 1+ -> 3+ is traversed by no path
+        std::fs::write(
+            &file_path,
+            "S\t1\tA\nS\t2\tC\nS\t3\tG\nS\t4\tT\n\
+             L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nL\t1\t+\t3\t+\t0M\nL\t3\t+\t4\t+\t0M\n\
+             P\ta\t1+,2+,3+\t*\nP\tb\t3+,4+\t*\n",
+        )
+        .unwrap();
+        let mask = |keep: bool| -> String {
+            let mut output: Vec<u8> = Vec::new();
+            write_masked(
+                &file_path,
+                &[String::from(if keep { "a" } else { "b" })],
+                &[],
+                &[],
+                keep,
+                false,
+                false,
+                &mut output,
+            )
+            .unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let kept: String = mask(true);
+        let masked: String = mask(false);
+        std::fs::remove_file(&file_path).unwrap();
+
+        assert_eq!(
+            kept,
+            "S\t1\tA\nS\t2\tC\nS\t3\tG\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nP\ta\t1+,2+,3+\t*\n"
+        );
+        // Without --keep, links between remaining segments stay unless -u is given
+        assert_eq!(
+            masked,
+            "S\t1\tA\nS\t2\tC\nS\t3\tG\n\
+             L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nL\t1\t+\t3\t+\t0M\nP\ta\t1+,2+,3+\t*\n"
+        );
     }
-    node
-}
 
-fn read_next_w_node(file: &mut BufReader<File>, buffer: &mut [u8; 1]) -> String {
-    /*
-     * Read the next node in the file, until a > or < is found
-     * file: the file to read
-     * buffer: a buffer to read the file
-     */
-    let mut node: String = String::new();
-    while file.read(buffer).unwrap() > 0 {
-        if buffer[0] == b'>' || buffer[0] == b'<' || buffer[0] == b'\t' {
-            break;
-        } else {
-            node.push(buffer[0] as char);
-        }
+    #[test]
+    fn test_mask_report() {
+        let file_path: String = temp_file("test_mask_report.gfa");
+        // Segment 4 has no sequence, its length comes from its LN tag
+        std::fs::write(
+            &file_path,
+            "S\t1\tA\nS\t2\tC\nS\t3\tG\nS\t4\t*\tLN:i:5\n\
+             L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nL\t3\t+\t4\t+\t0M\n\
+             P\ta\t1+,2+,3+\t*\nP\tb\t2+,3+,4+\t*\n",
+        )
+        .unwrap();
+        let mask = |name: &str| -> io::Result<Vec<(String, usize, u64, usize)>> {
+            write_masked(
+                &file_path,
+                &[String::from(name)],
+                &[],
+                &[],
+                false,
+                false,
+                false,
+                &mut Vec::new(),
+            )
+        };
+        let report: Vec<(String, usize, u64, usize)> = mask("b").unwrap();
+        let unknown_path: io::Result<Vec<(String, usize, u64, usize)>> = mask("c");
+        let keep_nothing: io::Result<Vec<(String, usize, u64, usize)>> = write_masked(
+            &file_path,
+            &[],
+            &[String::from("^HG")],
+            &[],
+            true,
+            false,
+            false,
+            &mut Vec::new(),
+        );
+        std::fs::remove_file(&file_path).unwrap();
+
+        assert_eq!(report, vec![(String::from("b"), 1, 5, 1)]);
+        assert_eq!(
+            unknown_path.unwrap_err().to_string(),
+            "Path not found in graph: c"
+        );
+        assert!(keep_nothing.is_err());
     }
-    node
 }
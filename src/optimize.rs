@@ -1,76 +1,349 @@
-use std::collections::HashMap;
+use crate::walks::{parse_p_steps, parse_w_steps};
+use clap::ValueEnum;
+use indexmap::IndexMap;
+use petgraph::algo::tarjan_scc;
+use petgraph::graphmap::DiGraphMap;
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, BufWriter, Write};
-use std::process::exit;
 
-pub fn relocate_ids(file_path: &str, output_file_node_ids: &str) -> io::Result<()> {
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Ordering {
+    /// Order of the S-lines in the file
+    File,
+    /// Topological order of the links (cycles are kept together)
+    Topological,
+    /// Order of first visit along the reference path, then along the other paths
+    Reference,
+    /// Breadth-first traversal of the links, starting from the first segment of the reference path
+    Bfs,
+}
+
+pub fn relocate_ids(
+    file_path: &str,
+    output_file_node_ids: &str,
+    ordering: Ordering,
+    reference: Option<&str>,
+) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    write_relocated(
+        file_path,
+        output_file_node_ids,
+        ordering,
+        reference,
+        &mut writer,
+    )?;
+    writer.flush()
+}
+
+fn write_relocated<W: Write>(
+    file_path: &str,
+    output_file_node_ids: &str,
+    ordering: Ordering,
+    reference: Option<&str>,
+    writer: &mut W,
+) -> io::Result<()> {
     /*
-    This function reads a GFA file and prints each line with modified IDs, and outputs to other file the node mapping
+    This function reads a GFA file and prints it with segments renumbered from 1, and outputs to other file the node mapping
+    Segments are numbered following the chosen ordering, and output records are sorted:
+    H-lines, then S-lines by ID, then L-lines by their IDs, then other lines in file order
+    All tags are kept, and P-lines, W-lines, C-lines and J-lines are rewritten
      */
     let file: File = File::open(file_path)?;
     let mut reader: BufReader<File> = BufReader::new(file);
-    let mut node_mappings: HashMap<u32, u32> = HashMap::new();
-    let output_file = File::create(output_file_node_ids).expect("unable to create file");
-    let mut file_writer = BufWriter::new(output_file);
     let mut line: String = String::new();
-    let mut e_lines: Vec<String> = Vec::new();
-    let mut new_id: u32 = 1;
+    let mut segments: IndexMap<String, usize> = IndexMap::new();
+    let mut links: Vec<(String, char, String, char)> = Vec::new();
+    let mut paths: Vec<(String, Vec<String>)> = Vec::new();
 
     while reader.read_line(&mut line)? > 0 {
-        let columns: Vec<&str> = line.split('\t').collect();
-        if let Some(first_char) = line.chars().next() {
-            if first_char == 'S' {
-                // Rename nodes given a linear contiguous attribution
-                let node_name: u32 = columns[1].parse::<u32>().unwrap();
-                write!(file_writer, "{}\t{}\n", node_name, new_id)
-                    .expect("Unable to write in output file.");
-                node_mappings.insert(node_name, new_id);
-                print!("{}\t{}\t{}", columns[0], new_id, columns[2]);
-                new_id += 1;
-            } else if first_char == 'L' {
-                // Store line for later
-                e_lines.push(line.clone());
-            } else if first_char == 'P' {
-                // Rename all nodes given the map
-                let mut chain: String = String::new();
-                let node_list: Vec<String> = columns[2]
-                    .trim()
-                    .split(',')
-                    .map(|s| s.to_string())
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        match line.chars().next() {
+            Some('S') => {
+                let index: usize = segments.len();
+                segments.insert(columns[1].to_string(), index);
+            }
+            Some('L') if matches!(ordering, Ordering::Topological | Ordering::Bfs) => {
+                links.push((
+                    columns[1].to_string(),
+                    columns[2].chars().next().unwrap_or('+'),
+                    columns[3].to_string(),
+                    columns[4].chars().next().unwrap_or('+'),
+                ));
+            }
+            // Only orderings guided by paths need their steps
+            Some('P') if matches!(ordering, Ordering::Reference | Ordering::Bfs) => {
+                let nodes: Vec<String> = parse_p_steps(columns[2])?
+                    .into_iter()
+                    .map(|(node, _)| node)
+                    .collect();
+                paths.push((columns[1].to_string(), nodes));
+            }
+            Some('W') if matches!(ordering, Ordering::Reference | Ordering::Bfs) => {
+                let nodes: Vec<String> = parse_w_steps(columns[6])?
+                    .into_iter()
+                    .map(|(node, _)| node)
                     .collect();
-                for node_desc in node_list {
-                    let (node, orientation) = node_desc.split_at(node_desc.len() - 1);
-                    if let Some(&mapped_id) = node_mappings.get(&node.parse::<u32>().unwrap()) {
-                        chain += &format!("{}{},", mapped_id, orientation);
-                    } else {
-                        eprintln!("Node {} not found in mappings", node);
-                        exit(1);
+                paths.push((
+                    format!("{}#{}#{}", columns[1], columns[2], columns[3]),
+                    nodes,
+                ));
+            }
+            _ => (),
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    let segment_index = |node: &str| -> io::Result<usize> {
+        segments.get(node).copied().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Segment not found in graph: {}", node),
+            )
+        })
+    };
+    // The reference path is moved first, so that the following orderings start from it
+    if let Some(reference) = reference {
+        match paths.iter().position(|(name, _)| name == reference) {
+            Some(position) => {
+                let reference_path = paths.remove(position);
+                paths.insert(0, reference_path);
+            }
+            None if matches!(ordering, Ordering::Reference | Ordering::Bfs) => {
+                eprintln!("Reference {} not found in graph", reference);
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Path not found in graph: {}", reference),
+                ));
+            }
+            None => (),
+        }
+    }
+
+    // Order in which segments (given by their index in the file) receive new IDs
+    let mut order: Vec<usize> = Vec::with_capacity(segments.len());
+    let mut visited: Vec<bool> = vec![false; segments.len()];
+    match ordering {
+        Ordering::File => order.extend(0..segments.len()),
+        Ordering::Topological => {
+            let mut graph: DiGraphMap<usize, ()> = DiGraphMap::new();
+            for index in 0..segments.len() {
+                graph.add_node(index);
+            }
+            for (from, from_orientation, to, to_orientation) in links.iter() {
+                let (from, to) = (segment_index(from)?, segment_index(to)?);
+                // A link read on the reverse strand goes backwards
+                if *from_orientation == '-' && *to_orientation == '-' {
+                    graph.add_edge(to, from, ());
+                } else {
+                    graph.add_edge(from, to, ());
+                }
+            }
+            // Strongly connected components come in reverse topological order
+            for mut component in tarjan_scc(&graph).into_iter().rev() {
+                component.sort_unstable();
+                order.extend(component);
+            }
+        }
+        Ordering::Reference => {
+            for (_, nodes) in paths.iter() {
+                for node in nodes.iter() {
+                    let index: usize = segment_index(node)?;
+                    if !visited[index] {
+                        visited[index] = true;
+                        order.push(index);
+                    }
+                }
+            }
+            order.extend((0..segments.len()).filter(|index| !visited[*index]));
+        }
+        Ordering::Bfs => {
+            let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); segments.len()];
+            for (from, _, to, _) in links.iter() {
+                let (from, to) = (segment_index(from)?, segment_index(to)?);
+                neighbors[from].push(to);
+                neighbors[to].push(from);
+            }
+            // Traversal starts from the first segment of the reference, then from unvisited segments
+            let start: Option<usize> = match paths.first().and_then(|(_, nodes)| nodes.first()) {
+                Some(node) => Some(segment_index(node)?),
+                None => None,
+            };
+            for root in start.into_iter().chain(0..segments.len()) {
+                if visited[root] {
+                    continue;
+                }
+                let mut queue: VecDeque<usize> = VecDeque::from([root]);
+                visited[root] = true;
+                while let Some(index) = queue.pop_front() {
+                    order.push(index);
+                    for neighbor in neighbors[index].iter() {
+                        if !visited[*neighbor] {
+                            visited[*neighbor] = true;
+                            queue.push_back(*neighbor);
+                        }
                     }
                 }
-                chain.truncate(chain.len() - 1);
-                println!("{}\t{}\t{}", columns[0], columns[1], chain);
-            } else if first_char == 'W' {
-                eprintln!("W-lines not implemented yet");
-                exit(1);
-            } else {
-                print!("{}", line)
             }
         }
+    }
+
+    let mut new_ids: Vec<usize> = vec![0; segments.len()];
+    for (position, index) in order.iter().enumerate() {
+        new_ids[*index] = position + 1;
+    }
+    let output_file = File::create(output_file_node_ids)?;
+    let mut file_writer = BufWriter::new(output_file);
+    for index in order.iter() {
+        let (node_name, _) = segments.get_index(*index).unwrap();
+        writeln!(file_writer, "{}\t{}", node_name, new_ids[*index])?;
+    }
+    file_writer.flush()?;
+    let map_id =
+        |node: &str| -> io::Result<String> { Ok(new_ids[segment_index(node)?].to_string()) };
+
+    // H-lines, S-lines and L-lines are gathered to be sorted
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut h_lines: Vec<String> = Vec::new();
+    let mut s_lines: Vec<(usize, String)> = Vec::new();
+    let mut l_lines: Vec<(usize, usize, String)> = Vec::new();
+    while reader.read_line(&mut line)? > 0 {
+        let mut columns: Vec<String> = line
+            .trim_end_matches(['\n', '\r'])
+            .split('\t')
+            .map(String::from)
+            .collect();
+        match line.chars().next() {
+            Some('H') => h_lines.push(columns.join("\t")),
+            Some('S') => {
+                let new_id: usize = new_ids[segment_index(&columns[1])?];
+                columns[1] = new_id.to_string();
+                s_lines.push((new_id, columns.join("\t")));
+            }
+            Some('L') => {
+                let from: usize = new_ids[segment_index(&columns[1])?];
+                let to: usize = new_ids[segment_index(&columns[3])?];
+                columns[1] = from.to_string();
+                columns[3] = to.to_string();
+                l_lines.push((from, to, columns.join("\t")));
+            }
+            _ => (),
+        }
         line.clear(); // Clear the line buffer for the next read
-                      // Treat L-lines
     }
-    for edge in e_lines {
-        let cols: Vec<&str> = edge.split('\t').collect();
-        if let (Some(&mapped_id_from), Some(&mapped_id_to)) = (
-            node_mappings.get(&cols[1].parse::<u32>().unwrap()),
-            node_mappings.get(&cols[3].parse::<u32>().unwrap()),
-        ) {
-            print!(
-                "{}\t{}\t{}\t{}\t{}\t{}",
-                cols[0], mapped_id_from, cols[2], mapped_id_to, cols[4], cols[5]
-            )
+    s_lines.sort_unstable_by_key(|(new_id, _)| *new_id);
+    l_lines.sort_by_key(|(from, to, _)| (*from, *to));
+    for line in h_lines.iter() {
+        writeln!(writer, "{}", line)?;
+    }
+    for (_, line) in s_lines.iter() {
+        writeln!(writer, "{}", line)?;
+    }
+    for (_, _, line) in l_lines.iter() {
+        writeln!(writer, "{}", line)?;
+    }
+
+    // Other lines are streamed in file order
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    while reader.read_line(&mut line)? > 0 {
+        let mut columns: Vec<String> = line
+            .trim_end_matches(['\n', '\r'])
+            .split('\t')
+            .map(String::from)
+            .collect();
+        match line.chars().next() {
+            Some('H') | Some('S') | Some('L') => {
+                line.clear();
+                continue;
+            }
+            Some('P') => {
+                let mut steps: Vec<String> = Vec::new();
+                for (node, orientation) in parse_p_steps(&columns[2])? {
+                    steps.push(format!("{}{}", map_id(&node)?, orientation));
+                }
+                columns[2] = steps.join(",");
+            }
+            Some('W') => {
+                let mut walk: String = String::new();
+                for (node, orientation) in parse_w_steps(&columns[6])? {
+                    walk.push(orientation);
+                    walk += &map_id(&node)?;
+                }
+                columns[6] = walk;
+            }
+            Some('C') | Some('J') => {
+                columns[1] = map_id(&columns[1])?;
+                columns[3] = map_id(&columns[3])?;
+            }
+            _ => (),
         }
+        writeln!(writer, "{}", columns.join("\t"))?;
+        line.clear(); // Clear the line buffer for the next read
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+
+    #[test]
+    fn test_write_relocated() {
+        let directory: std::path::PathBuf = temp_dir("test_write_relocated");
+        let file_path: String = directory.join("graph.gfa").to_string_lossy().to_string();
+        let mapping_path: String = directory.join("mapping.tsv").to_string_lossy().to_string();
+        // 1 -> 2 -> 3 on the reference, and 1 -> 4 on alt (link written on the reverse strand)
+        std::fs::write(
+            &file_path,
+            "H\tVN:Z:1.0\nS\t3\tA\nS\t1\tC\nS\t2\tG\nS\t4\tT\n\
+             L\t2\t+\t3\t+\t0M\nL\t1\t+\t2\t+\t0M\nL\t4\t-\t1\t-\t0M\n\
+             P\tref\t1+,2+,3+\t*\nP\talt\t1+,4+\t*\n",
+        )
+        .unwrap();
+        let relocate = |ordering: Ordering, reference: Option<&str>| -> (String, String) {
+            let mut output: Vec<u8> = Vec::new();
+            write_relocated(&file_path, &mapping_path, ordering, reference, &mut output).unwrap();
+            (
+                String::from_utf8(output).unwrap(),
+                std::fs::read_to_string(&mapping_path).unwrap(),
+            )
+        };
+        let (file_output, file_mapping) = relocate(Ordering::File, None);
+        let (_, topological_mapping) = relocate(Ordering::Topological, None);
+        let (_, reference_mapping) = relocate(Ordering::Reference, Some("alt"));
+        let (_, bfs_mapping) = relocate(Ordering::Bfs, Some("ref"));
+        let missing_reference: io::Result<()> = write_relocated(
+            &file_path,
+            &mapping_path,
+            Ordering::Reference,
+            Some("chr1"),
+            &mut Vec::new(),
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(file_mapping, "3\t1\n1\t2\n2\t3\n4\t4\n");
+        assert_eq!(
+            file_output,
+            "H\tVN:Z:1.0\nS\t1\tA\nS\t2\tC\nS\t3\tG\nS\t4\tT\n\
+             L\t2\t+\t3\t+\t0M\nL\t3\t+\t1\t+\t0M\nL\t4\t-\t2\t-\t0M\n\
+             P\tref\t2+,3+,1+\t*\nP\talt\t2+,4+\t*\n"
+        );
+        // Any order where each segment comes after its predecessors along the links
+        let position = |node: &str| -> usize {
+            topological_mapping
+                .lines()
+                .position(|line| line.starts_with(&format!("{}\t", node)))
+                .unwrap()
+        };
+        assert!(position("1") < position("2") && position("2") < position("3"));
+        assert!(position("1") < position("4"));
+        assert_eq!(reference_mapping, "1\t1\n4\t2\n2\t3\n3\t4\n");
+        assert_eq!(bfs_mapping, "1\t1\n2\t2\n4\t3\n3\t4\n");
+        assert!(missing_reference.is_err());
+    }
+}
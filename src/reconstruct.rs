@@ -1,12 +1,28 @@
+use crate::sequence::reverse_complement;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 
-pub fn reconstruct_paths(file_path: &str) -> io::Result<()> {
+pub fn reconstruct_paths(file_path: &str, low_memory: bool) -> io::Result<()> {
     /*
     Reconstruct paths in the graph as their fasta sequences
     For each path in the graph, it will print to standard output the fasta sequence of the path
     The fasta sequence is made of the concatenation, in the right orientation, of each node described in the path
+    In low memory mode, sequences are not stored but read back from the file using their byte offsets
+     */
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    if low_memory {
+        reconstruct_paths_from_offsets(file_path, &mut writer)?;
+    } else {
+        reconstruct_paths_in_memory(file_path, &mut writer)?;
+    }
+    writer.flush()
+}
+
+fn reconstruct_paths_in_memory<W: Write>(file_path: &str, writer: &mut W) -> io::Result<()> {
+    /*
+    Stores every segment sequence in memory, and streams each path sequence to the writer
      */
     let file: File = File::open(file_path)?;
     let mut reader: BufReader<File> = BufReader::new(file);
@@ -23,41 +39,126 @@ pub fn reconstruct_paths(file_path: &str) -> io::Result<()> {
                 seq_sequences.insert(node_name, sequence);
             }
             if first_char == 'P' {
-                let path_name: String = String::from(columns[1]);
-                let mut path_sequence: String = String::new();
-                let node_list: Vec<String> = columns[2]
-                    .trim()
-                    .split(',')
-                    .map(|s| s.to_string())
-                    .collect();
-                for node_desc in node_list {
+                writeln!(writer, ">{}", columns[1])?;
+                for node_desc in columns[2].trim().split(',') {
                     let (node, orientation) = node_desc.split_at(node_desc.len() - 1);
-                    let sequence: &String = seq_sequences.get(node).unwrap();
+                    let sequence: &String = seq_sequences.get(node).ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("Segment not found in graph: {}", node),
+                        )
+                    })?;
                     if orientation == "+" {
-                        path_sequence.push_str(sequence);
+                        writer.write_all(sequence.as_bytes())?;
                     } else {
-                        path_sequence.push_str(&reverse_complement(sequence));
+                        writer.write_all(reverse_complement(sequence).as_bytes())?;
                     }
                 }
-                println!(">{}", path_name);
-                println!("{}", path_sequence);
+                writeln!(writer)?;
+            }
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+    Ok(())
+}
+
+fn reconstruct_paths_from_offsets<W: Write>(file_path: &str, writer: &mut W) -> io::Result<()> {
+    /*
+    Only stores, for each segment, the offset and the length of its sequence in the file
+    Each segment is read back from the file when it is encountered in a path, so memory usage
+    is bounded by the number of segments and the length of the longest segment
+     */
+    let seq_offsets: HashMap<String, (u64, usize)> = index_sequences(file_path)?;
+    let mut sequences: BufReader<File> = BufReader::new(File::open(file_path)?);
+    let mut buffer: Vec<u8> = Vec::new();
+
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line: String = String::new();
+
+    while reader.read_line(&mut line)? > 0 {
+        if line.starts_with('P') {
+            let columns: Vec<&str> = line.split('\t').collect();
+            writeln!(writer, ">{}", columns[1])?;
+            for node_desc in columns[2].trim().split(',') {
+                let (node, orientation) = node_desc.split_at(node_desc.len() - 1);
+                let (offset, length) = seq_offsets.get(node).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("Segment not found in graph: {}", node),
+                    )
+                })?;
+                buffer.resize(*length, 0);
+                sequences.seek(SeekFrom::Start(*offset))?;
+                sequences.read_exact(&mut buffer)?;
+                let sequence: &str = std::str::from_utf8(&buffer)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                if orientation == "+" {
+                    writer.write_all(sequence.as_bytes())?;
+                } else {
+                    writer.write_all(reverse_complement(sequence).as_bytes())?;
+                }
             }
+            writeln!(writer)?;
         }
         line.clear(); // Clear the line buffer for the next read
     }
     Ok(())
 }
 
-fn reverse_complement(sequence: &String) -> String {
-    let mut reverse_complement: String = String::new();
-    for base in sequence.chars().rev() {
-        match base {
-            'A' => reverse_complement.push('T'),
-            'C' => reverse_complement.push('G'),
-            'G' => reverse_complement.push('C'),
-            'T' => reverse_complement.push('A'),
-            _ => reverse_complement.push(base),
+fn index_sequences(file_path: &str) -> io::Result<HashMap<String, (u64, usize)>> {
+    /*
+    Given a file path, this function reads the GFA file and returns a HashMap:
+    - seq_offsets: node names as keys, offset and length of the sequence in the file as values
+    */
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut seq_offsets: HashMap<String, (u64, usize)> = HashMap::new();
+    let mut position: u64 = 0;
+    let mut line: String = String::new();
+
+    loop {
+        let bytes_read: usize = reader.read_line(&mut line)?;
+        if bytes_read == 0 {
+            break;
         }
+        if line.starts_with('S') {
+            let columns: Vec<&str> = line.split('\t').collect();
+            let offset: u64 = position + columns[0].len() as u64 + columns[1].len() as u64 + 2;
+            let length: usize = columns[2].trim_end().len();
+            seq_offsets.insert(columns[1].to_string(), (offset, length));
+        }
+        position += bytes_read as u64;
+        line.clear(); // Clear the line buffer for the next read
+    }
+    Ok(seq_offsets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_file;
+
+    #[test]
+    fn test_low_memory_matches_in_memory() {
+        let file_path: String = temp_file("reconstruct.gfa");
+        std::fs::write(
+            &file_path,
+            "H\tVN:Z:1.0\nS\t1\tACGT\tLN:i:4\nS\t2\tGG\nL\t1\t+\t2\t-\t0M\nS\t3\tTTAC\nP\tfirst\t1+,2-,3+\t*\nP\tsecond\t3-,1+\t*\n",
+        )
+        .unwrap();
+        let mut in_memory: Vec<u8> = Vec::new();
+        reconstruct_paths_in_memory(&file_path, &mut in_memory).unwrap();
+        let mut from_offsets: Vec<u8> = Vec::new();
+        reconstruct_paths_from_offsets(&file_path, &mut from_offsets).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+        assert_eq!(
+            String::from_utf8(in_memory).unwrap(),
+            ">first\nACGTCCTTAC\n>second\nGTAAACGT\n"
+        );
+        assert_eq!(
+            String::from_utf8(from_offsets).unwrap(),
+            ">first\nACGTCCTTAC\n>second\nGTAAACGT\n"
+        );
     }
-    return reverse_complement;
 }
@@ -0,0 +1,279 @@
+use crate::gaf::open_alignments;
+use crate::walks::{parse_p_steps, parse_w_steps};
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// GFA graph: segments, links, containments, jumps, paths and walks are renamed
+    Gfa,
+    /// GAF alignments: oriented nodes of the path column are renamed
+    Gaf,
+    /// Tab-separated output of this tool, with node IDs in the given columns
+    Tsv,
+}
+
+pub fn remap_ids(
+    file_path: &str,
+    mapping_file: &str,
+    invert: bool,
+    format: Option<Format>,
+    columns: &[usize],
+    graph: Option<&str>,
+) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    write_remapped(
+        file_path,
+        mapping_file,
+        invert,
+        format,
+        columns,
+        graph,
+        &mut writer,
+    )?;
+    writer.flush()
+}
+
+fn write_remapped<W: Write>(
+    file_path: &str,
+    mapping_file: &str,
+    invert: bool,
+    format: Option<Format>,
+    columns: &[usize],
+    graph: Option<&str>,
+    writer: &mut W,
+) -> io::Result<()> {
+    /*
+    This function reads a file and prints it with node IDs translated through a mapping file
+    - mapping_file: old and new IDs separated by a tab, as written by Opitmize, or graph,
+      old and new IDs as written by Concatenate (graph selects the rows of one input graph)
+    - invert: translate new IDs back to old ones
+    - format: GFA, GAF or TSV, guessed from the file extension if not given
+    - columns: 1-based columns holding node IDs in a TSV file (lines starting with '#' are kept)
+    Gzip-compressed files are read transparently, and printed uncompressed
+    IDs missing from the mapping are kept unchanged, and counted on stderr
+     */
+    let mapping: HashMap<String, String> = read_id_mapping(mapping_file, invert, graph)?;
+    let format: Format = format.unwrap_or_else(|| {
+        let extension: &str = file_path
+            .trim_end_matches(".gz")
+            .rsplit('.')
+            .next()
+            .unwrap_or_default();
+        match extension {
+            "gfa" => Format::Gfa,
+            "gaf" => Format::Gaf,
+            _ => Format::Tsv,
+        }
+    });
+
+    let mut reader: Box<dyn BufRead> = open_alignments(file_path)?;
+    let mut line: String = String::new();
+    let mut missing: usize = 0;
+    let mut map_id = |node: &str| -> String {
+        match mapping.get(node) {
+            Some(new_id) => new_id.clone(),
+            None => {
+                missing += 1;
+                node.to_string()
+            }
+        }
+    };
+
+    while reader.read_line(&mut line)? > 0 {
+        if line.starts_with('#') || line.trim_end().is_empty() {
+            write!(writer, "{}", line)?;
+            line.clear();
+            continue;
+        }
+        let mut fields: Vec<String> = line
+            .trim_end_matches(['\n', '\r'])
+            .split('\t')
+            .map(String::from)
+            .collect();
+        match format {
+            Format::Gfa => match fields[0].as_str() {
+                "S" => fields[1] = map_id(&fields[1]),
+                "L" | "C" | "J" => {
+                    fields[1] = map_id(&fields[1]);
+                    fields[3] = map_id(&fields[3]);
+                }
+                "P" => {
+                    let steps: Vec<String> = parse_p_steps(&fields[2])?
+                        .into_iter()
+                        .map(|(node, orientation)| format!("{}{}", map_id(&node), orientation))
+                        .collect();
+                    fields[2] = steps.join(",");
+                }
+                "W" => {
+                    fields[6] = parse_w_steps(&fields[6])?
+                        .into_iter()
+                        .map(|(node, orientation)| format!("{}{}", orientation, map_id(&node)))
+                        .collect();
+                }
+                _ => (),
+            },
+            // Paths made of a stable sequence name instead of oriented nodes are kept
+            Format::Gaf if fields.len() > 5 && fields[5].starts_with(['>', '<']) => {
+                fields[5] = parse_w_steps(&fields[5])?
+                    .into_iter()
+                    .map(|(node, orientation)| format!("{}{}", orientation, map_id(&node)))
+                    .collect();
+            }
+            Format::Gaf => (),
+            Format::Tsv => {
+                for column in columns.iter() {
+                    if let Some(field) = fields.get_mut(column.saturating_sub(1)) {
+                        *field = map_id(field);
+                    }
+                }
+            }
+        }
+        writeln!(writer, "{}", fields.join("\t"))?;
+        line.clear(); // Clear the line buffer for the next read
+    }
+    if missing > 0 {
+        eprintln!("{} node IDs were not found in {}", missing, mapping_file);
+    }
+
+    Ok(())
+}
+
+fn read_id_mapping(
+    mapping_file: &str,
+    invert: bool,
+    graph: Option<&str>,
+) -> io::Result<HashMap<String, String>> {
+    /*
+    Reads a mapping between node IDs, with two (old, new) or three (graph, old, new) columns
+    Three-column mappings need the graph to read the IDs of
+     */
+    let file: File = File::open(mapping_file)?;
+    let reader: BufReader<File> = BufReader::new(file);
+    let mut mapping: HashMap<String, String> = HashMap::new();
+    let mut duplicates: usize = 0;
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line: String = line?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        let (old_id, new_id) = match columns[..] {
+            [old_id, new_id] => (old_id, new_id),
+            [graph_name, old_id, new_id] => match graph {
+                Some(graph) if graph == graph_name => (old_id, new_id),
+                Some(_) => continue,
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "{} maps the IDs of several graphs, the graph to remap must be given",
+                            mapping_file
+                        ),
+                    ))
+                }
+            },
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Line {} of {} does not have two or three tab-separated columns",
+                        line_number + 1,
+                        mapping_file
+                    ),
+                ))
+            }
+        };
+        let (key, value) = if invert {
+            (new_id, old_id)
+        } else {
+            (old_id, new_id)
+        };
+        // Merged segments give several old IDs for a new one: the first one is kept
+        if mapping.contains_key(key) {
+            duplicates += 1;
+            continue;
+        }
+        mapping.insert(key.to_string(), value.to_string());
+    }
+    if duplicates > 0 {
+        eprintln!(
+            "{} IDs are mapped several times in {}, only their first mapping is used",
+            duplicates, mapping_file
+        );
+    }
+    Ok(mapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{temp_dir, temp_file};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    #[test]
+    fn test_remap_gzipped_gaf() {
+        let directory: std::path::PathBuf = temp_dir("test_remap_gaf");
+        let alignments: String = directory
+            .join("reads.gaf.gz")
+            .to_string_lossy()
+            .into_owned();
+        let mapping_file: String = directory.join("mapping.tsv").to_string_lossy().into_owned();
+        std::fs::write(
+            &mapping_file,
+            "chr1.gfa\t1\t1\nchr2.gfa\t1\t5\nchr2.gfa\t2\t6\n",
+        )
+        .unwrap();
+        let mut encoder: GzEncoder<File> =
+            GzEncoder::new(File::create(&alignments).unwrap(), Compression::default());
+        encoder
+            .write_all(
+                b"read1\t4\t0\t4\t+\t>1<2\t6\t2\t6\t4\t4\t60\n\
+                read2\t4\t0\t4\t+\tchr1\t4\t0\t4\t4\t4\t60\n",
+            )
+            .unwrap();
+        encoder.finish().unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        let result: io::Result<()> = write_remapped(
+            &alignments,
+            &mapping_file,
+            false,
+            None,
+            &[1],
+            Some("chr2.gfa"),
+            &mut output,
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(result.is_ok());
+        // Paths given by a sequence name are kept unchanged
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "read1\t4\t0\t4\t+\t>5<6\t6\t2\t6\t4\t4\t60\n\
+             read2\t4\t0\t4\t+\tchr1\t4\t0\t4\t4\t4\t60\n"
+        );
+    }
+
+    #[test]
+    fn test_read_id_mapping() {
+        let mapping_file: String = temp_file("test_read_id_mapping.tsv");
+        std::fs::write(&mapping_file, "chr1.gfa\t1\t1\nchr2.gfa\t1\t5\n").unwrap();
+        let mapping: HashMap<String, String> =
+            read_id_mapping(&mapping_file, true, Some("chr2.gfa")).unwrap();
+        let no_graph: io::Result<HashMap<String, String>> =
+            read_id_mapping(&mapping_file, false, None);
+        std::fs::remove_file(&mapping_file).unwrap();
+
+        assert_eq!(
+            mapping,
+            HashMap::from([(String::from("5"), String::from("1"))])
+        );
+        // IDs of different graphs can't be told apart without the graph
+        assert!(no_graph.is_err());
+    }
+}
@@ -6,7 +6,7 @@ pub fn remove_loops(file_path: &str, threshold: u8) {
     let paths: HashMap<String, HashMap<String, u8>> = read_paths(file_path, threshold).unwrap();
     let max_label: u64 = get_max_label(file_path);
     let mut available_label: u64 = max_label + 1;
-    let mut aggregated_occurences: HashMap<String, u8> = aggregate_occurences(paths);
+    let aggregated_occurences: HashMap<String, u8> = aggregate_occurences(paths);
 
     // Allocate node labels in vectors for each node with multiple occurences.
     // Number of labels is equal to the aggregated_occurences count
@@ -24,6 +24,7 @@ pub fn remove_loops(file_path: &str, threshold: u8) {
     println!("Added labels: {}", available_label - max_label);
 }
 
+#[allow(dead_code)]
 fn print_hashmap(hashmap: HashMap<String, Vec<u64>>) {
     for (node, labels) in hashmap.iter() {
         print!("{}: ", node);
@@ -40,7 +41,6 @@ fn get_node_count(file_path: &str) -> u64 {
     let mut node_count: u64 = 0;
     for line in reader.lines() {
         let line = line.unwrap();
-        let columns: Vec<&str> = line.split('\t').collect();
         if let Some(first_char) = line.chars().next() {
             if first_char == 'S' {
                 node_count += 1;
@@ -69,7 +69,8 @@ fn read_next_node<R: Read>(file: &mut BufReader<R>, buffer: &mut [u8; 1]) -> Str
     node
 }
 
-fn add_nodes_to_graph(file_path: &str, node_labels: HashMap<String, Vec<u64>>) {
+#[allow(dead_code)]
+fn add_nodes_to_graph(file_path: &str, _node_labels: HashMap<String, Vec<u64>>) {
     /*
     Adding a node to the graph implies to create a label, copy the sequence and add the node to the graph
     Reads each S line of the GFA.
@@ -77,7 +78,7 @@ fn add_nodes_to_graph(file_path: &str, node_labels: HashMap<String, Vec<u64>>) {
     If the node is a key of node_labels, create per occurence in the vector a new node
     Else, only print the line without any modification
      */
-    let file = File::open(file_path).unwrap();
+    let _file = File::open(file_path).unwrap();
     // TODO
 }
 
@@ -85,18 +86,22 @@ fn count_number_occurences_in_path<R: Read>(
     file: &mut BufReader<R>,
     buffer: &mut [u8; 1],
 ) -> HashMap<String, u8> {
-    let mut number_nodes_in_path: u64 = 0;
-    let mut occurences: HashMap<String, u8> = HashMap::new();
-    loop {
+    // Nodes are counted as they are read, without storing the path
+    count_occurences(std::iter::from_fn(|| {
         let node = read_next_node(file, buffer);
-        if node.is_empty() {
-            break;
-        }
-        let count = occurences.entry(node.to_string()).or_insert(0);
-        *count += 1;
-        number_nodes_in_path += 1;
+        (!node.is_empty()).then_some(node)
+    }))
+}
+
+pub fn count_occurences<S: AsRef<str>>(nodes: impl IntoIterator<Item = S>) -> HashMap<String, u8> {
+    /*
+    Counts the visits of a path to each of its nodes, saturating at 255
+     */
+    let mut occurences: HashMap<String, u8> = HashMap::new();
+    for node in nodes {
+        let count = occurences.entry(node.as_ref().to_string()).or_insert(0);
+        *count = count.saturating_add(1);
     }
-    println!("Number of nodes in path: {}", number_nodes_in_path);
     occurences
 }
 
@@ -190,7 +195,7 @@ fn index_paths(file_path: &str) -> io::Result<HashMap<String, u64>> {
             }
             if first_char == 'P' {
                 let path_name = String::from(columns[1]);
-                let offset = reader.seek(io::SeekFrom::Current(0))?
+                let offset = reader.stream_position()?
                     - (line.len() as u64 - columns[0].len() as u64 - columns[1].len() as u64 - 2);
                 path_positions.insert(path_name, offset);
             }
@@ -226,12 +231,13 @@ mod tests {
         assert_eq!(occurences.get("3"), Some(&1));
     }
 
+    #[test]
     fn test_filter_occurences() {
         let mut occurences: HashMap<String, u8> = HashMap::new();
         occurences.insert("1".to_string(), 2);
         occurences.insert("2".to_string(), 3);
         occurences.insert("3".to_string(), 1);
-        let filtered_occurences = filter_occurences(occurences, 2);
+        let filtered_occurences = filter_occurences(occurences, 1);
         assert_eq!(filtered_occurences.get("1"), Some(&2));
         assert_eq!(filtered_occurences.get("2"), Some(&3));
         assert_eq!(filtered_occurences.get("3"), None);
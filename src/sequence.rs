@@ -0,0 +1,78 @@
+// Sequence utilities shared by the commands that read or merge segment sequences.
+// Complements follow the IUPAC nucleotide alphabet and preserve case, so soft-masked
+// (lowercase) bases stay soft-masked once reverse-complemented.
+
+pub fn complement(base: u8) -> u8 {
+    /*
+    Returns the IUPAC complement of a base, keeping its case
+    Symbols that are not part of the alphabet (gaps, '*', ...) are returned unchanged
+     */
+    match base {
+        b'A' => b'T',
+        b'T' => b'A',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        b'a' => b't',
+        b't' => b'a',
+        b'c' => b'g',
+        b'g' => b'c',
+        b'r' => b'y',
+        b'y' => b'r',
+        b'k' => b'm',
+        b'm' => b'k',
+        b'b' => b'v',
+        b'v' => b'b',
+        b'd' => b'h',
+        b'h' => b'd',
+        // S, W, N and their lowercase versions are their own complement
+        _ => base,
+    }
+}
+
+pub fn reverse_complement(sequence: &str) -> String {
+    /*
+    Computes the reverse complement of a sequence
+     */
+    sequence
+        .bytes()
+        .rev()
+        .map(|base| complement(base) as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement("ACGT"), "ACGT");
+        assert_eq!(reverse_complement("AAcgN"), "NcgTT");
+        assert_eq!(reverse_complement("RYKMBVDHSW"), "WSDHBVKMRY");
+        assert_eq!(reverse_complement("ac-gt"), "ac-gt");
+    }
+
+    proptest! {
+        #[test]
+        fn test_reverse_complement_is_involution(sequence in "[ACGTRYKMBVDHSWNacgtrykmbvdhswn*-]{0,64}") {
+            prop_assert_eq!(reverse_complement(&reverse_complement(&sequence)), sequence);
+        }
+
+        #[test]
+        fn test_reverse_complement_keeps_case(sequence in "[ACGTRYKMBVDHSWNacgtrykmbvdhswn]{0,64}") {
+            let reversed: String = reverse_complement(&sequence);
+            for (base, complemented) in sequence.chars().rev().zip(reversed.chars()) {
+                prop_assert_eq!(base.is_lowercase(), complemented.is_lowercase());
+            }
+        }
+    }
+}
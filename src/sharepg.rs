@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use crate::table::{ColumnType, OutputFormat, TableWriter, Value};
+use indexmap::IndexMap;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
 /*
@@ -41,15 +42,16 @@ fn add_interval(interval: Interval, intervals: &mut Vec<Interval>) {
 
 pub fn shared_nodes(
     file_path: &str,
-    include: &Vec<String>,
-    exclude: &Vec<String>,
+    include: &[String],
+    exclude: &[String],
     sensitivity: f64,
+    format: OutputFormat,
 ) -> io::Result<()> {
     /*
     This function reads a GFA file ad two lists of paths
     The list of paths to include designates the paths that must be shared by the nodes, up to a given sensitivity ratio
     The list of paths to exclude designates the paths that must not be shared by the nodes, up to 1 - a given sensitivity ratio
-    It prints the paths that share the given intervals
+    It prints the paths that share the given intervals, with a column per path telling if the node is in the path
      */
 
     // We create for each node a vector of boolean values, one for each path in the include and the exclude lists
@@ -58,7 +60,7 @@ pub fn shared_nodes(
 
     let file: File = File::open(file_path)?;
     let mut reader: BufReader<File> = BufReader::new(file);
-    let mut segments_vectors: HashMap<String, Vec<bool>> = HashMap::new();
+    let mut segments_vectors: IndexMap<String, Vec<bool>> = IndexMap::new();
     let mut line: String = String::new();
     let paths: Vec<String> = include
         .iter()
@@ -66,10 +68,18 @@ pub fn shared_nodes(
         .map(|s| s.to_string())
         .collect();
 
-    println!(
-        "# NodeName\tStatus\tIncludeRatio\tExcludeRatio\t{:?}",
+    let mut columns: Vec<(&str, ColumnType)> = vec![
+        ("NodeName", ColumnType::Text),
+        ("Status", ColumnType::Boolean),
+        ("IncludeRatio", ColumnType::Float),
+        ("ExcludeRatio", ColumnType::Float),
+    ];
+    columns.extend(
         paths
+            .iter()
+            .map(|path| (path.as_str(), ColumnType::Boolean)),
     );
+    let mut table: TableWriter = TableWriter::new(format, &columns)?;
     while reader.read_line(&mut line)? > 0 {
         let columns: Vec<&str> = line.split('\t').collect();
         if let Some(first_char) = line.chars().next() {
@@ -91,12 +101,10 @@ pub fn shared_nodes(
                     let (node, _orientation) = node_desc.split_at(node_desc.len() - 1);
                     // Edit the correct boolean position in the corresponding vector
                     let boolean_vector: &mut Vec<bool> = segments_vectors.get_mut(node).unwrap();
-                    let mut i: usize = 0;
-                    for path in paths.iter() {
+                    for (i, path) in paths.iter().enumerate() {
                         if path == &path_name {
                             boolean_vector[i] = true;
                         }
-                        i += 1;
                     }
                 }
             }
@@ -125,11 +133,15 @@ pub fn shared_nodes(
         let shared: bool = (include_ratio.is_nan() || include_ratio >= sensitivity)
             && (exclude_ratio.is_nan() || exclude_ratio <= 1.0 - sensitivity);
 
-        println!(
-            "{}\t{}\t{}\t{}\t{:?}",
-            node, shared as i32, include_ratio, exclude_ratio, boolean_vector
-        );
+        let mut row: Vec<Value> = vec![
+            Value::Text(node.clone()),
+            Value::Boolean(shared),
+            Value::Float(include_ratio),
+            Value::Float(exclude_ratio),
+        ];
+        row.extend(boolean_vector.iter().map(|b| Value::Boolean(*b == 1)));
+        table.write_row(row)?;
     }
 
-    Ok(())
+    table.finish()
 }
@@ -36,7 +36,7 @@ fn load_graph(file_path: &str) -> Result<Graph<String, String>, io::Error> {
                 // In the case of an E-line, we store the predecessor and successor nodes
                 let predecessor: NodeIndex = backbone.add_node(String::from(columns[1]));
                 let successor: NodeIndex = backbone.add_node(String::from(columns[3]));
-                backbone.add_edge(predecessor, successor, String::from(columns[1..4].concat()));
+                backbone.add_edge(predecessor, successor, columns[1..4].concat());
             }
             line.clear(); // Clear the line buffer for the next read
         }
@@ -45,9 +45,9 @@ fn load_graph(file_path: &str) -> Result<Graph<String, String>, io::Error> {
     Ok(backbone)
 }
 
-fn write_graph(file_path: &str) {}
+fn write_graph(_file_path: &str) {}
 
-fn simp_substitution(graph: Graph<String, String>) {}
+fn simp_substitution(_graph: Graph<String, String>) {}
 
 fn simp_loops() {}
 
@@ -1,8 +1,16 @@
+use crate::sequence::reverse_complement;
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
 
 pub fn prune_spurious_breakpoints(file_path: &str) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    write_pruned(file_path, &mut writer)?;
+    writer.flush()
+}
+
+fn write_pruned<W: Write>(file_path: &str, writer: &mut W) -> io::Result<()> {
     /*
     Given a file path, this function reads the GFA file and returns a HashMap:
     - spurious_nodes: a vector of spurious node IDs as values
@@ -45,77 +53,80 @@ pub fn prune_spurious_breakpoints(file_path: &str) -> io::Result<()> {
                 } else {
                     -columns[3].parse::<i32>().unwrap()
                 };
-                // We update the Hashmaps with the nodes
-                if seq_successors.contains_key(&from_node) {
-                    if seq_successors
-                        .get_mut(&from_node)
-                        .unwrap()
-                        .contains(&to_node)
-                    {
-                        continue;
-                    } else {
-                        seq_successors
-                            .get_mut(&from_node)
-                            .unwrap()
-                            .push(to_node.clone());
+                // We update the Hashmaps with the nodes, on both strands as -y -> -x is the same link
+                for (from_node, to_node) in [(from_node, to_node), (-to_node, -from_node)] {
+                    let successors: &mut Vec<i32> = seq_successors.entry(from_node).or_default();
+                    if !successors.contains(&to_node) {
+                        successors.push(to_node);
                     }
-                } else {
-                    seq_successors.insert(from_node.clone(), vec![to_node.clone()]);
-                }
-                if seq_predecessors.contains_key(&to_node) {
-                    if seq_predecessors
-                        .get_mut(&to_node)
-                        .unwrap()
-                        .contains(&from_node)
-                    {
-                        continue;
-                    } else {
-                        seq_predecessors
-                            .get_mut(&to_node)
-                            .unwrap()
-                            .push(from_node.clone());
+                    let predecessors: &mut Vec<i32> = seq_predecessors.entry(to_node).or_default();
+                    if !predecessors.contains(&from_node) {
+                        predecessors.push(from_node);
                     }
-                } else {
-                    seq_predecessors.insert(to_node.clone(), vec![from_node.clone()]);
                 }
             }
             line.clear(); // Clear the line buffer for the next read
         }
     }
-    let mut node_pairs: HashSet<Vec<u32>> = HashSet::new();
+    // Junctions are stored in both directions: x -> y is the same junction as -y -> -x
+    let mut node_pairs: HashMap<i32, i32> = HashMap::new();
     // STEP 2 : two-pass filter
     eprintln!("STEP 2 --- two-pass filter");
     for (node, successors) in seq_successors.iter() {
-        // checking if x has only one successor y accessed by the same sign
-        if successors.len() == 1 && node.signum() == successors[0].signum() {
+        // checking if x has only one successor y, in either orientation
+        if successors.len() == 1 && node.abs() != successors[0].abs() {
             // then checking if either reverse y has no reverse successors or have reverse x as a sole reverse successor
-            if !seq_successors.contains_key(&-successors[0])
+            if (!seq_successors.contains_key(&-successors[0])
                 || seq_successors
                     .get(&-successors[0])
-                    .map_or(false, |v| v.len() == 1 && v[0] == -node)
-            {
-                if seq_predecessors
+                    .is_some_and(|v| v.len() == 1 && v[0] == -node))
+                && seq_predecessors
                     .get(&successors[0])
-                    .map_or(false, |v| v.len() == 1)
-                {
-                    node_pairs.insert(vec![node.abs() as u32, successors[0].abs() as u32]);
-                }
+                    .is_some_and(|v| v.len() == 1)
+            {
+                node_pairs.insert(*node, successors[0]);
+                node_pairs.insert(-successors[0], -node);
             }
         }
     }
     // STEP 3: We need to merge chains of spurious
     eprintln!("STEP 3 --- popping spurious breakpoints");
-    let mut pop_vect: Vec<u32> = Vec::new();
-    // easiest way is to retain a Hashmap of node IDs, change them as we go through merging
-    for pair in node_pairs.iter() {
-        // update sequence of the node and delete other node
-        // /!\ takes currently into account only forward direction
-        let new_sequence:String = format!("{:?}{:?}",nodes_sequences.get(&pair[0]),nodes_sequences.get(&pair[1]));
-        nodes_sequences.insert(pair[0], new_sequence);
-        pop_vect.push(pair[1]);
-        nodes_sequences.remove(&pair[1]);
-        // update mapping
-        mapping.insert(pair[1], pair[0]);
+    let mut heads: Vec<i32> = node_pairs
+        .keys()
+        .filter(|node| !node_pairs.contains_key(&-*node))
+        .copied()
+        .collect();
+    // Forward heads are preferred, so that chains read forward keep the ID of their first node
+    heads.sort_unstable_by_key(|node| (*node < 0, node.unsigned_abs()));
+    let mut merged: HashSet<u32> = HashSet::new();
+    // Merged nodes whose forward strand is read reversed in the node they were merged into
+    let mut flipped: HashSet<u32> = HashSet::new();
+    // Chains are merged from their first node, which keeps its ID
+    // Each chain has a head at both of its ends, the first one met is used
+    for head in heads {
+        if merged.contains(&head.unsigned_abs()) {
+            continue;
+        }
+        let mut chain: Vec<i32> = vec![head];
+        let mut current: i32 = head;
+        while let Some(next) = node_pairs.get(&current) {
+            chain.push(*next);
+            current = *next;
+        }
+        let new_sequence: String = merge_chain(&chain, &nodes_sequences);
+        let head_id: u32 = head.unsigned_abs();
+        for node in chain.iter() {
+            merged.insert(node.unsigned_abs());
+        }
+        for node in chain[1..].iter() {
+            nodes_sequences.remove(&node.unsigned_abs());
+            // update mapping
+            mapping.insert(node.unsigned_abs(), head_id);
+            if (*node < 0) != (head < 0) {
+                flipped.insert(node.unsigned_abs());
+            }
+        }
+        nodes_sequences.insert(head_id, new_sequence);
     }
 
     // STEP 4: write output file
@@ -135,14 +146,20 @@ pub fn prune_spurious_breakpoints(file_path: &str) -> io::Result<()> {
             if first_char == 'S' {
                 let node_id: u32 = columns[1].parse::<u32>().unwrap();
                 if mapping.get(&node_id) == Some(&node_id) {
-                    println!("S\t{}\t{}",node_id,nodes_sequences.get(&node_id).unwrap())
+                    writeln!(writer, "S\t{}\t{}",node_id,nodes_sequences.get(&node_id).unwrap())?;
                 }
             }
             else if first_char == 'L' {
-                let in_node: u32 = resolve_dep(&mapping,columns[1].parse::<u32>().unwrap());
-                let out_node: u32 = resolve_dep(&mapping,columns[3].parse::<u32>().unwrap());
+                let in_id: u32 = columns[1].parse::<u32>().unwrap();
+                let out_id: u32 = columns[3].parse::<u32>().unwrap();
+                let in_node: u32 = resolve_dep(&mapping,in_id);
+                let out_node: u32 = resolve_dep(&mapping,out_id);
                 if in_node != out_node {
-                    println!("L\t{}\t{}\t{}\t{}\t{}",mapping.get(&in_node).unwrap(),columns[2],mapping.get(&out_node).unwrap(),columns[4],columns[5])
+                    // Ends of the link on a node read reversed in its merged node change orientation
+                    let orientation = |node: &u32, orientation: &str| -> char {
+                        if (orientation == "+") != flipped.contains(node) { '+' } else { '-' }
+                    };
+                    writeln!(writer, "L\t{}\t{}\t{}\t{}\t{}",in_node,orientation(&in_id,columns[2]),out_node,orientation(&out_id,columns[4]),columns[5])?;
                 }
             }
             else if first_char == 'P' {
@@ -152,7 +169,7 @@ pub fn prune_spurious_breakpoints(file_path: &str) -> io::Result<()> {
                     .map(|s| s.to_string())
                     .collect();
                 node_list.retain(|s| mapping.get(&(s[0..s.len() - 1].parse::<u32>().unwrap())) == Some(&(s[0..s.len() - 1].parse::<u32>().unwrap())));
-                println!("P\t{}\t{}\t*",columns[1],node_list.join(","));
+                writeln!(writer, "P\t{}\t{}\t*",columns[1],node_list.join(","))?;
             }
             else if first_char == 'W' {
                 let mut node_list: Vec<String> = columns[6][1..]
@@ -161,12 +178,12 @@ pub fn prune_spurious_breakpoints(file_path: &str) -> io::Result<()> {
                     .map(|s| s.to_string())
                     .collect();
                 // we add char on first item
-                node_list[0] = String::from(columns[2].chars().nth(0).unwrap()) + &node_list[0];
+                node_list[0] = String::from(columns[2].chars().next().unwrap()) + &node_list[0];
                 node_list.retain(|s| mapping.get(&(s[1..].parse::<u32>().unwrap())) == Some(&(s[1..].parse::<u32>().unwrap())));
-                println!("W\t{}\t{}\t{}\t{}\t{}\t{}",columns[1],columns[2],columns[3],columns[4],columns[5],node_list.join(""));
+                writeln!(writer, "W\t{}\t{}\t{}\t{}\t{}\t{}",columns[1],columns[2],columns[3],columns[4],columns[5],node_list.join(""))?;
             }
             else {
-                println!("{}",line);
+                writeln!(writer, "{}",line)?;
             }
         line.clear(); // Clear the line buffer for the next read
         }
@@ -180,8 +197,70 @@ fn resolve_dep(mapping: &HashMap<u32,u32>, start:u32) -> u32 {
      */
     let mut id:u32 = start;
     while Some(&id) != mapping.get(&id) {
-        eprintln!("{} != {} ? {}", id,mapping.get(&id).unwrap(),Some(&id) != mapping.get(&id));
         id = *mapping.get(&id).unwrap();
     }
-    return id;
-}
\ No newline at end of file
+    id
+}
+
+fn merge_chain(chain: &[i32], nodes_sequences: &HashMap<u32, String>) -> String {
+    /*
+    Spells a chain of oriented nodes (negative IDs being reverse-oriented), and returns the
+    sequence of its first node in forward orientation once the chain is merged into it
+     */
+    let spelled: String = chain
+        .iter()
+        .map(|node| {
+            let sequence: &String = nodes_sequences.get(&node.unsigned_abs()).unwrap();
+            if *node > 0 {
+                sequence.clone()
+            } else {
+                reverse_complement(sequence)
+            }
+        })
+        .collect();
+    if chain[0] > 0 {
+        spelled
+    } else {
+        reverse_complement(&spelled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_file;
+
+    #[test]
+    fn test_merge_chain() {
+        let nodes_sequences: HashMap<u32, String> = HashMap::from([
+            (1, String::from("AAC")),
+            (2, String::from("Gt")),
+            (3, String::from("RN")),
+        ]);
+        assert_eq!(merge_chain(&[1, 2, 3], &nodes_sequences), "AACGtRN");
+        // -1 -> -2 spells rc(1) rc(2), so 1 becomes 2 followed by 1
+        assert_eq!(merge_chain(&[-1, -2], &nodes_sequences), "GtAAC");
+        assert_eq!(merge_chain(&[-3, -2, -1], &nodes_sequences), "AACGtRN");
+    }
+
+    #[test]
+    fn test_write_pruned() {
+        let file_path: String = temp_file("test_write_pruned.gfa");
+        // 1+ -> 2- is merged into 1, 2- -> 3+ being written from 3 on the reverse strand
+        std::fs::write(
+            &file_path,
+            "S\t1\tAAC\nS\t2\tGGT\nS\t3\tTT\nS\t4\tC\n\
+             L\t1\t+\t2\t-\t0M\nL\t3\t-\t2\t+\t0M\nL\t4\t+\t3\t+\t0M\n",
+        )
+        .unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        write_pruned(&file_path, &mut output).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "S\t1\tAACACC\nS\t3\tTT\nS\t4\tC\n\
+             L\t3\t-\t1\t-\t0M\nL\t4\t+\t3\t+\t0M\n"
+        );
+    }
+}
@@ -0,0 +1,361 @@
+use crate::table::json_string;
+use crate::walks::{read_lengths, read_next_p_step, read_next_w_step};
+use indexmap::IndexMap;
+use petgraph::unionfind::UnionFind;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+struct Stats {
+    segments: usize,
+    links: usize,
+    paths: usize,
+    walks: usize,
+    total_length: u64,
+    n50: u64,
+    // Number of segments per length bin, keyed by the lower bound of the bin (1, 10, 100, ...),
+    // segments without length being in their own bin (0)
+    length_histogram: BTreeMap<u64, usize>,
+    degrees: BTreeMap<usize, usize>,
+    components: usize,
+    self_loops: usize,
+    dead_ends: usize,
+    tips: usize,
+    // Sequence (bp) covered by exactly k paths, for k in 0..=n
+    coverage: Vec<u64>,
+}
+
+pub fn stats_gfa(file_path: &str, json: bool) -> io::Result<()> {
+    /*
+    This function reads a GFA file and prints a summary of the graph
+    - counts of segments, links, paths and walks, and total sequence length
+    - N50 and length histogram (by powers of ten) of the segments
+    - degree distribution, computed on both sides of the segments
+    - connected components, self-loops, dead ends (segment sides without links)
+      and tips (segments with links on a single side)
+    - sequence covered by 0, 1, ..., n distinct paths
+    Output is a two-column TSV, or a JSON object if asked
+    Segments without sequence ('*') count for the length given by their LN tag
+     */
+    let stats: Stats = compute_stats(file_path)?;
+    if json {
+        println!("{}", json_stats(&stats));
+    } else {
+        print_tsv(&stats);
+    }
+    Ok(())
+}
+
+fn compute_stats(file_path: &str) -> io::Result<Stats> {
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line: String = String::new();
+
+    let mut segments: IndexMap<String, u64> = IndexMap::new();
+    let mut links: Vec<(String, char, String, char)> = Vec::new();
+
+    while reader.read_line(&mut line)? > 0 {
+        let columns: Vec<&str> = line.trim_end().split('\t').collect();
+        match line.chars().next() {
+            Some('S') if columns.len() > 2 => {
+                segments.insert(columns[1].to_string(), seq_lengths[columns[1]]);
+            }
+            Some('L') if columns.len() > 4 => {
+                links.push((
+                    columns[1].to_string(),
+                    columns[2].chars().next().unwrap_or('+'),
+                    columns[3].to_string(),
+                    columns[4].chars().next().unwrap_or('+'),
+                ));
+            }
+            _ => (),
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    // Links are counted on the side of the segment they leave from or arrive to
+    let mut left_degree: Vec<usize> = vec![0; segments.len()];
+    let mut right_degree: Vec<usize> = vec![0; segments.len()];
+    let mut components: UnionFind<usize> = UnionFind::new(segments.len());
+    let mut self_loops: usize = 0;
+    for (from, from_orientation, to, to_orientation) in links.iter() {
+        let (Some(from_index), Some(to_index)) =
+            (segments.get_index_of(from), segments.get_index_of(to))
+        else {
+            eprintln!(
+                "Link between {} and {} refers to a missing segment",
+                from, to
+            );
+            continue;
+        };
+        if *from_orientation == '+' {
+            right_degree[from_index] += 1;
+        } else {
+            left_degree[from_index] += 1;
+        }
+        if *to_orientation == '+' {
+            left_degree[to_index] += 1;
+        } else {
+            right_degree[to_index] += 1;
+        }
+        if from_index == to_index {
+            self_loops += 1;
+        }
+        components.union(from_index, to_index);
+    }
+
+    let mut stats: Stats = Stats {
+        segments: segments.len(),
+        links: links.len(),
+        paths: 0,
+        walks: 0,
+        total_length: segments.values().sum(),
+        n50: 0,
+        length_histogram: BTreeMap::new(),
+        degrees: BTreeMap::new(),
+        components: 0,
+        self_loops,
+        dead_ends: 0,
+        tips: 0,
+        coverage: Vec::new(),
+    };
+
+    let mut lengths: Vec<u64> = segments.values().copied().collect();
+    lengths.sort_unstable_by(|a, b| b.cmp(a));
+    let mut cumulated_length: u64 = 0;
+    for length in lengths.iter() {
+        cumulated_length += length;
+        if 2 * cumulated_length >= stats.total_length {
+            stats.n50 = *length;
+            break;
+        }
+    }
+    for length in lengths.iter() {
+        let mut bin: u64 = if *length == 0 { 0 } else { 1 };
+        while bin > 0 && bin * 10 <= *length {
+            bin *= 10;
+        }
+        *stats.length_histogram.entry(bin).or_default() += 1;
+    }
+    for index in 0..segments.len() {
+        *stats
+            .degrees
+            .entry(left_degree[index] + right_degree[index])
+            .or_default() += 1;
+        let free_sides: usize =
+            (left_degree[index] == 0) as usize + (right_degree[index] == 0) as usize;
+        stats.dead_ends += free_sides;
+        if free_sides == 1 {
+            stats.tips += 1;
+        }
+    }
+    let mut roots: Vec<usize> = components.into_labeling();
+    roots.sort_unstable();
+    roots.dedup();
+    stats.components = roots.len();
+
+    // Second pass, counting the distinct paths that go through each segment
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut path_counts: Vec<usize> = vec![0; segments.len()];
+    let mut last_path: Vec<usize> = vec![usize::MAX; segments.len()];
+    let mut buffer: [u8; 1] = [0];
+
+    loop {
+        if reader.read(&mut buffer)? == 0 {
+            break;
+        }
+        let first_char: u8 = buffer[0];
+        if first_char == b'\n' {
+            continue;
+        }
+        if first_char != b'P' && first_char != b'W' {
+            reader.read_line(&mut line)?;
+            line.clear(); // Clear the line buffer for the next read
+            continue;
+        }
+        let path_index: usize = stats.paths + stats.walks;
+        // Skips the fields before the path description
+        let skipped_fields: usize = if first_char == b'P' { 2 } else { 6 };
+        for _ in 0..skipped_fields {
+            reader.read_until(b'\t', &mut Vec::new())?;
+        }
+        loop {
+            let step: Option<(String, char)> = if first_char == b'P' {
+                read_next_p_step(&mut reader)?
+            } else {
+                read_next_w_step(&mut reader)?
+            };
+            let Some((node, _)) = step else {
+                break;
+            };
+            if let Some(index) = segments.get_index_of(&node) {
+                if last_path[index] != path_index {
+                    last_path[index] = path_index;
+                    path_counts[index] += 1;
+                }
+            }
+        }
+        if first_char == b'P' {
+            stats.paths += 1;
+        } else {
+            stats.walks += 1;
+        }
+        reader.read_line(&mut line)?;
+        line.clear(); // Clear the line buffer for the next read
+    }
+    stats.coverage = vec![0; stats.paths + stats.walks + 1];
+    for (index, length) in segments.values().enumerate() {
+        stats.coverage[path_counts[index]] += length;
+    }
+    Ok(stats)
+}
+
+fn fraction(length: u64, total_length: u64) -> f64 {
+    if total_length == 0 {
+        0.0
+    } else {
+        length as f64 / total_length as f64
+    }
+}
+
+fn print_tsv(stats: &Stats) {
+    println!("# Statistic\tValue");
+    println!("Segments\t{}", stats.segments);
+    println!("Links\t{}", stats.links);
+    println!("Paths\t{}", stats.paths);
+    println!("Walks\t{}", stats.walks);
+    println!("TotalLength\t{}", stats.total_length);
+    println!("N50\t{}", stats.n50);
+    println!("Components\t{}", stats.components);
+    println!("SelfLoops\t{}", stats.self_loops);
+    println!("DeadEnds\t{}", stats.dead_ends);
+    println!("Tips\t{}", stats.tips);
+    for (bin, count) in stats.length_histogram.iter() {
+        println!("SegmentLength:{}\t{}", bin_label(*bin), count);
+    }
+    for (degree, count) in stats.degrees.iter() {
+        println!("Degree:{}\t{}", degree, count);
+    }
+    for (paths, length) in stats.coverage.iter().enumerate() {
+        println!("CoveredBy:{}\t{}", paths, length);
+        println!(
+            "CoveredFraction:{}\t{:.6}",
+            paths,
+            fraction(*length, stats.total_length)
+        );
+    }
+}
+
+fn bin_label(bin: u64) -> String {
+    /*
+    Label of a bin of the length histogram: the range of lengths it holds
+     */
+    if bin == 0 {
+        String::from("0")
+    } else {
+        format!("{}-{}", bin, bin * 10 - 1)
+    }
+}
+
+fn json_stats(stats: &Stats) -> String {
+    /*
+    Formats the statistics as a JSON object, with one key per line
+     */
+    let object = |entries: Vec<(String, String)>| -> String {
+        let fields: Vec<String> = entries
+            .iter()
+            .map(|(key, value)| format!("{}: {}", json_string(key), value))
+            .collect();
+        format!("{{{}}}", fields.join(", "))
+    };
+    let histogram: String = object(
+        stats
+            .length_histogram
+            .iter()
+            .map(|(bin, count)| (bin_label(*bin), count.to_string()))
+            .collect(),
+    );
+    let degrees: String = object(
+        stats
+            .degrees
+            .iter()
+            .map(|(degree, count)| (degree.to_string(), count.to_string()))
+            .collect(),
+    );
+    let coverage: Vec<String> = stats
+        .coverage
+        .iter()
+        .enumerate()
+        .map(|(paths, length)| {
+            object(vec![
+                (String::from("paths"), paths.to_string()),
+                (String::from("length"), length.to_string()),
+                (
+                    String::from("fraction"),
+                    format!("{:.6}", fraction(*length, stats.total_length)),
+                ),
+            ])
+        })
+        .collect();
+    let fields: Vec<(&str, String)> = vec![
+        ("segments", stats.segments.to_string()),
+        ("links", stats.links.to_string()),
+        ("paths", stats.paths.to_string()),
+        ("walks", stats.walks.to_string()),
+        ("total_length", stats.total_length.to_string()),
+        ("n50", stats.n50.to_string()),
+        ("components", stats.components.to_string()),
+        ("self_loops", stats.self_loops.to_string()),
+        ("dead_ends", stats.dead_ends.to_string()),
+        ("tips", stats.tips.to_string()),
+        ("segment_length_histogram", histogram),
+        ("degree_distribution", degrees),
+        ("coverage", format!("[{}]", coverage.join(", "))),
+    ];
+    let lines: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("  {}: {}", json_string(key), value))
+        .collect();
+    format!("{{\n{}\n}}", lines.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_file;
+
+    #[test]
+    fn test_compute_stats() {
+        let file_path: String = temp_file("stats.gfa");
+        // 3 has no sequence but a length of 30, 4 has a self-loop, 5 is isolated,
+        // 6 has neither sequence nor length
+        std::fs::write(
+            &file_path,
+            "S\t1\tACGT\nS\t2\tGG\nS\t3\t*\tLN:i:30\nS\t4\tA\nS\t5\tCC\nS\t6\t*\n\
+            L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nL\t3\t+\t4\t+\t0M\nL\t4\t+\t4\t+\t0M\n\
+            P\tp1\t1+,2+,3+\t*\nW\tHG1\t1\tchr1\t0\t36\t>1>2>3\nP\tp2\t1+\t*\n",
+        )
+        .unwrap();
+        let stats: Stats = compute_stats(&file_path).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+        assert_eq!(
+            (stats.segments, stats.links, stats.paths, stats.walks),
+            (6, 4, 2, 1)
+        );
+        assert_eq!((stats.total_length, stats.n50), (39, 30));
+        assert_eq!((stats.components, stats.self_loops), (3, 1));
+        // Free sides: left of 1 (the only tip), both sides of 5 and 6
+        assert_eq!((stats.dead_ends, stats.tips), (5, 1));
+        assert_eq!(stats.coverage, vec![3, 0, 32, 4]);
+        assert_eq!(
+            stats.length_histogram,
+            BTreeMap::from([(0, 1), (1, 4), (10, 1)])
+        );
+        let json: String = json_stats(&stats);
+        assert!(json.starts_with("{\n  \"segments\": 6,\n"));
+        assert!(json.contains("\"segment_length_histogram\": {\"0\": 1, \"1-9\": 4, \"10-99\": 1}"));
+        assert!(json.contains("{\"paths\": 3, \"length\": 4, \"fraction\": 0.102564}"));
+    }
+}
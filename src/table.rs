@@ -0,0 +1,329 @@
+use arrow_array::builder::{BooleanBuilder, Float64Builder, Int64Builder, StringBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use clap::ValueEnum;
+use std::collections::HashSet;
+use std::io::{self, BufWriter, Stdout, Write};
+use std::sync::Arc;
+
+// Rows buffered before writing an Arrow record batch
+const BATCH_SIZE: usize = 65536;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Tab-separated values, with a header line starting with '#'
+    Tsv,
+    /// One JSON object per line, with a key per column
+    Jsonl,
+    /// Arrow IPC file (Feather v2), readable by pandas and polars
+    Arrow,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColumnType {
+    Text,
+    Integer,
+    Float,
+    Boolean,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    Text(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    // Written as '.' in TSV, null in JSON and Arrow; NaN and infinite floats are written as missing
+    Missing,
+}
+
+enum ColumnBuilder {
+    Text(StringBuilder),
+    Integer(Int64Builder),
+    Float(Float64Builder),
+    Boolean(BooleanBuilder),
+}
+
+struct ArrowOutput {
+    file_writer: FileWriter<BufWriter<Stdout>>,
+    schema: Arc<Schema>,
+    builders: Vec<ColumnBuilder>,
+}
+
+pub struct TableWriter {
+    format: OutputFormat,
+    columns: Vec<(String, ColumnType)>,
+    writer: BufWriter<Stdout>,
+    arrow: Option<ArrowOutput>,
+    rows: usize,
+}
+
+impl TableWriter {
+    pub fn new(format: OutputFormat, columns: &[(&str, ColumnType)]) -> io::Result<Self> {
+        /*
+        Starts a table on stdout with the given columns, writing the header in TSV
+        Column names must be unique, as JSON keys and Arrow fields are looked up by name
+         */
+        let mut names: HashSet<&str> = HashSet::new();
+        if let Some((name, _)) = columns.iter().find(|(name, _)| !names.insert(name)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Duplicate column name: {}", name),
+            ));
+        }
+        let columns: Vec<(String, ColumnType)> = columns
+            .iter()
+            .map(|(name, column_type)| (name.to_string(), *column_type))
+            .collect();
+        let mut writer: BufWriter<Stdout> = BufWriter::new(io::stdout());
+        let mut arrow: Option<ArrowOutput> = None;
+        match format {
+            OutputFormat::Tsv => {
+                let names: Vec<&str> = columns.iter().map(|(name, _)| name.as_str()).collect();
+                writeln!(writer, "# {}", names.join("\t"))?;
+            }
+            OutputFormat::Jsonl => (),
+            OutputFormat::Arrow => {
+                let fields: Vec<Field> = columns
+                    .iter()
+                    .map(|(name, column_type)| {
+                        let data_type: DataType = match column_type {
+                            ColumnType::Text => DataType::Utf8,
+                            ColumnType::Integer => DataType::Int64,
+                            ColumnType::Float => DataType::Float64,
+                            ColumnType::Boolean => DataType::Boolean,
+                        };
+                        Field::new(name, data_type, true)
+                    })
+                    .collect();
+                let schema: Arc<Schema> = Arc::new(Schema::new(fields));
+                let file_writer = FileWriter::try_new(BufWriter::new(io::stdout()), &schema)
+                    .map_err(arrow_error)?;
+                arrow = Some(ArrowOutput {
+                    file_writer,
+                    schema,
+                    builders: column_builders(&columns),
+                });
+            }
+        }
+        Ok(TableWriter {
+            format,
+            columns,
+            writer,
+            arrow,
+            rows: 0,
+        })
+    }
+
+    pub fn write_row(&mut self, row: Vec<Value>) -> io::Result<()> {
+        if row.len() != self.columns.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Row has {} values for {} columns",
+                    row.len(),
+                    self.columns.len()
+                ),
+            ));
+        }
+        // NaN and infinite numbers are not valid JSON, they are missing in all formats
+        let row: Vec<Value> = row.into_iter().map(missing_if_not_finite).collect();
+        match self.format {
+            OutputFormat::Tsv => {
+                let fields: Vec<String> = row.iter().map(tsv_field).collect();
+                writeln!(self.writer, "{}", fields.join("\t"))?;
+            }
+            OutputFormat::Jsonl => {
+                let fields: Vec<String> = self
+                    .columns
+                    .iter()
+                    .zip(row.iter())
+                    .map(|((name, _), value)| {
+                        format!("{}:{}", json_string(name), json_value(value))
+                    })
+                    .collect();
+                writeln!(self.writer, "{{{}}}", fields.join(","))?;
+            }
+            OutputFormat::Arrow => {
+                let arrow: &mut ArrowOutput = self.arrow.as_mut().unwrap();
+                for (builder, value) in arrow.builders.iter_mut().zip(row) {
+                    match (builder, value) {
+                        (ColumnBuilder::Text(builder), Value::Text(text)) => {
+                            builder.append_value(text)
+                        }
+                        (ColumnBuilder::Integer(builder), Value::Integer(integer)) => {
+                            builder.append_value(integer)
+                        }
+                        (ColumnBuilder::Float(builder), Value::Float(float)) => {
+                            builder.append_value(float)
+                        }
+                        (ColumnBuilder::Boolean(builder), Value::Boolean(boolean)) => {
+                            builder.append_value(boolean)
+                        }
+                        (ColumnBuilder::Text(builder), Value::Missing) => builder.append_null(),
+                        (ColumnBuilder::Integer(builder), Value::Missing) => builder.append_null(),
+                        (ColumnBuilder::Float(builder), Value::Missing) => builder.append_null(),
+                        (ColumnBuilder::Boolean(builder), Value::Missing) => builder.append_null(),
+                        _ => {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                "Value does not match the type of its column",
+                            ))
+                        }
+                    }
+                }
+                self.rows += 1;
+                if self.rows == BATCH_SIZE {
+                    self.write_batch()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> io::Result<()> {
+        /*
+        Writes the buffered rows as an Arrow record batch
+         */
+        let arrow: &mut ArrowOutput = self.arrow.as_mut().unwrap();
+        let arrays: Vec<ArrayRef> = arrow
+            .builders
+            .iter_mut()
+            .map(|builder| -> ArrayRef {
+                match builder {
+                    ColumnBuilder::Text(builder) => Arc::new(builder.finish()),
+                    ColumnBuilder::Integer(builder) => Arc::new(builder.finish()),
+                    ColumnBuilder::Float(builder) => Arc::new(builder.finish()),
+                    ColumnBuilder::Boolean(builder) => Arc::new(builder.finish()),
+                }
+            })
+            .collect();
+        let batch: RecordBatch =
+            RecordBatch::try_new(arrow.schema.clone(), arrays).map_err(arrow_error)?;
+        arrow.file_writer.write(&batch).map_err(arrow_error)?;
+        self.rows = 0;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        /*
+        Writes the remaining rows, and the footer of an Arrow file
+         */
+        if self.arrow.is_some() {
+            if self.rows > 0 {
+                self.write_batch()?;
+            }
+            let mut arrow: ArrowOutput = self.arrow.take().unwrap();
+            arrow.file_writer.finish().map_err(arrow_error)?;
+            arrow.file_writer.get_mut().flush()?;
+        }
+        self.writer.flush()
+    }
+}
+
+fn missing_if_not_finite(value: Value) -> Value {
+    match value {
+        Value::Float(float) if !float.is_finite() => Value::Missing,
+        value => value,
+    }
+}
+
+fn tsv_field(value: &Value) -> String {
+    match value {
+        Value::Text(text) => text.clone(),
+        Value::Integer(integer) => integer.to_string(),
+        Value::Float(float) => format!("{:.4}", float),
+        Value::Boolean(boolean) => (*boolean as i32).to_string(),
+        Value::Missing => String::from("."),
+    }
+}
+
+fn json_value(value: &Value) -> String {
+    match value {
+        Value::Text(text) => json_string(text),
+        Value::Integer(integer) => integer.to_string(),
+        Value::Float(float) => float.to_string(),
+        Value::Boolean(boolean) => boolean.to_string(),
+        Value::Missing => String::from("null"),
+    }
+}
+
+fn column_builders(columns: &[(String, ColumnType)]) -> Vec<ColumnBuilder> {
+    columns
+        .iter()
+        .map(|(_, column_type)| match column_type {
+            ColumnType::Text => ColumnBuilder::Text(StringBuilder::new()),
+            ColumnType::Integer => ColumnBuilder::Integer(Int64Builder::new()),
+            ColumnType::Float => ColumnBuilder::Float(Float64Builder::new()),
+            ColumnType::Boolean => ColumnBuilder::Boolean(BooleanBuilder::new()),
+        })
+        .collect()
+}
+
+fn arrow_error(error: ArrowError) -> io::Error {
+    io::Error::other(error.to_string())
+}
+
+pub fn json_string(text: &str) -> String {
+    /*
+    Quotes a string for JSON, escaping quotes, backslashes and control characters
+     */
+    let mut quoted: String = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for character in text.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            character if (character as u32) < 0x20 => {
+                quoted.push_str(&format!("\\u{:04x}", character as u32))
+            }
+            character => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("HG002#1#chr1"), "\"HG002#1#chr1\"");
+        assert_eq!(json_string("a\"b\\c\td"), "\"a\\\"b\\\\c\\td\"");
+        assert_eq!(json_string("\u{1}"), "\"\\u0001\"");
+    }
+
+    #[test]
+    fn test_missing_values() {
+        // A NaN ratio is missing in every format, instead of NaN in TSV and null in JSON
+        for float in [f64::NAN, f64::INFINITY] {
+            let value: Value = missing_if_not_finite(Value::Float(float));
+            assert_eq!(value, Value::Missing);
+            assert_eq!(tsv_field(&value), ".");
+            assert_eq!(json_value(&value), "null");
+        }
+        let value: Value = missing_if_not_finite(Value::Float(0.5));
+        assert_eq!(tsv_field(&value), "0.5000");
+        assert_eq!(json_value(&value), "0.5");
+    }
+
+    #[test]
+    fn test_duplicate_columns() {
+        let columns: [(&str, ColumnType); 3] = [
+            ("NodeName", ColumnType::Text),
+            ("HG1#1#chr1", ColumnType::Boolean),
+            ("HG1#1#chr1", ColumnType::Boolean),
+        ];
+        let error: io::Error = TableWriter::new(OutputFormat::Jsonl, &columns)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Duplicate column name: HG1#1#chr1");
+        assert!(TableWriter::new(OutputFormat::Jsonl, &columns[..2]).is_ok());
+    }
+}
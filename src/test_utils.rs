@@ -0,0 +1,21 @@
+// Fixtures shared by the tests of all modules
+use std::path::PathBuf;
+
+pub fn temp_file(name: &str) -> String {
+    /*
+    Path of a file in the temporary directory, prefixed by the process ID so that test runs don't collide
+     */
+    std::env::temp_dir()
+        .join(format!("{}_{}", std::process::id(), name))
+        .to_string_lossy()
+        .into_owned()
+}
+
+pub fn temp_dir(name: &str) -> PathBuf {
+    /*
+    Creates a directory in the temporary directory, prefixed by the process ID, and returns its path
+     */
+    let directory: PathBuf = PathBuf::from(temp_file(name));
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
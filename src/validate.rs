@@ -0,0 +1,523 @@
+use crate::walks::parse_w_steps;
+use indexmap::IndexMap;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+// Number of problems of each kind, with the output they are reported to
+struct Problems<'a> {
+    counts: IndexMap<&'static str, usize>,
+    writer: &'a mut dyn Write,
+}
+
+pub fn validate_gfa(file_path: &str) -> io::Result<usize> {
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    let total: usize = write_validation(file_path, &mut writer)?;
+    writer.flush()?;
+    Ok(total)
+}
+
+fn write_validation(file_path: &str, writer: &mut dyn Write) -> io::Result<usize> {
+    /*
+    This function reads a GFA file and checks its consistency, reporting every problem found
+    instead of stopping at the first one. Problems are printed with their line number, followed
+    by a summary of the number of problems per kind. Returns the total number of problems.
+    Checked properties:
+    - header version, and W-lines only used with GFA1.1 and later
+    - number of mandatory fields per line, syntax of path steps and tags
+    - duplicate segment IDs, and sequence lengths matching LN tags
+    - segments referenced by links, paths and walks exist
+    - consecutive steps of paths and walks are supported by links in the right orientation
+    - W-lines coordinates are consistent with the length of the walk
+     */
+    writeln!(writer, "# LineNumber\tKind\tProblem")?;
+    let mut problems: Problems = Problems {
+        counts: IndexMap::new(),
+        writer,
+    };
+    let mut seq_lengths: HashMap<String, u64> = HashMap::new();
+    let mut links: HashSet<(String, char, String, char)> = HashSet::new();
+    let mut version: Option<String> = None;
+
+    // First pass: headers, segments and links
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line: String = String::new();
+    let mut line_number: usize = 0;
+    while reader.read_line(&mut line)? > 0 {
+        line_number += 1;
+        let columns: Vec<&str> = line.trim_end_matches(['\n', '\r']).split('\t').collect();
+        match columns[0] {
+            "H" => {
+                check_tags(&mut problems, line_number, &columns[1..])?;
+                for tag in columns[1..].iter() {
+                    if let Some(value) = tag.strip_prefix("VN:Z:") {
+                        if !["1.0", "1.1", "1.2"].contains(&value) {
+                            report(
+                                &mut problems,
+                                line_number,
+                                "version",
+                                format!("Unsupported GFA version {}", value),
+                            )?;
+                        }
+                        version = Some(value.to_string());
+                    }
+                }
+            }
+            "S" if check_fields(&mut problems, line_number, &columns, 3)? => {
+                let mut length: u64 = columns[2].len() as u64;
+                for tag in columns[3..].iter() {
+                    if let Some(Ok(tag_length)) = tag.strip_prefix("LN:i:").map(str::parse::<u64>) {
+                        if columns[2] == "*" {
+                            // Sequence is not stored, the length is given by the tag
+                            length = tag_length;
+                        } else if tag_length != length {
+                            report(
+                                &mut problems,
+                                line_number,
+                                "length",
+                                format!(
+                                    "Segment {} has a sequence of length {} but LN:i:{}",
+                                    columns[1], length, tag_length
+                                ),
+                            )?;
+                        }
+                    }
+                }
+                check_tags(&mut problems, line_number, &columns[3..])?;
+                if seq_lengths.insert(columns[1].to_string(), length).is_some() {
+                    report(
+                        &mut problems,
+                        line_number,
+                        "duplicate",
+                        format!("Segment {} is defined more than once", columns[1]),
+                    )?;
+                }
+            }
+            "L" if check_fields(&mut problems, line_number, &columns, 6)? => {
+                let from_orientation: Option<char> = parse_orientation(columns[2]);
+                let to_orientation: Option<char> = parse_orientation(columns[4]);
+                if let (Some(from_orientation), Some(to_orientation)) =
+                    (from_orientation, to_orientation)
+                {
+                    links.insert((
+                        columns[1].to_string(),
+                        from_orientation,
+                        columns[3].to_string(),
+                        to_orientation,
+                    ));
+                } else {
+                    report(
+                        &mut problems,
+                        line_number,
+                        "orientation",
+                        format!(
+                            "Invalid link orientations {} and {}",
+                            columns[2], columns[4]
+                        ),
+                    )?;
+                }
+                check_tags(&mut problems, line_number, &columns[6..])?;
+            }
+            _ => (),
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    // Second pass: references to segments, paths and walks
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line_number: usize = 0;
+    while reader.read_line(&mut line)? > 0 {
+        line_number += 1;
+        let columns: Vec<&str> = line.trim_end_matches(['\n', '\r']).split('\t').collect();
+        match columns[0] {
+            "L" if columns.len() >= 6 => {
+                for node in [columns[1], columns[3]] {
+                    if !seq_lengths.contains_key(node) {
+                        report(
+                            &mut problems,
+                            line_number,
+                            "missing",
+                            format!("Link references unknown segment {}", node),
+                        )?;
+                    }
+                }
+            }
+            "P" if check_fields(&mut problems, line_number, &columns, 3)? => {
+                let steps: Vec<(String, char)> =
+                    read_p_steps(&mut problems, line_number, columns[1], columns[2])?;
+                check_steps(
+                    &mut problems,
+                    line_number,
+                    columns[1],
+                    &steps,
+                    &seq_lengths,
+                    &links,
+                )?;
+                check_tags(&mut problems, line_number, &columns[4.min(columns.len())..])?;
+            }
+            "W" => {
+                if version.as_deref() == Some("1.0") {
+                    report(
+                        &mut problems,
+                        line_number,
+                        "version",
+                        String::from("W-lines require GFA1.1 or later, but header is VN:Z:1.0"),
+                    )?;
+                }
+                if check_fields(&mut problems, line_number, &columns, 7)? {
+                    let path_name: String = format!("{}#{}#{}", columns[1], columns[2], columns[3]);
+                    let steps: Vec<(String, char)> =
+                        read_w_steps(&mut problems, line_number, &path_name, columns[6])?;
+                    let walk_length: u64 = check_steps(
+                        &mut problems,
+                        line_number,
+                        &path_name,
+                        &steps,
+                        &seq_lengths,
+                        &links,
+                    )?;
+                    match (columns[4].parse::<u64>(), columns[5].parse::<u64>()) {
+                        (Ok(start), Ok(end)) => {
+                            if end < start || end - start != walk_length {
+                                report(
+                                    &mut problems,
+                                    line_number,
+                                    "coordinates",
+                                    format!(
+                                        "Walk {} spans {}-{} but its sequence has length {}",
+                                        path_name, start, end, walk_length
+                                    ),
+                                )?;
+                            }
+                        }
+                        _ if columns[4] == "*" || columns[5] == "*" => (),
+                        _ => report(
+                            &mut problems,
+                            line_number,
+                            "coordinates",
+                            format!(
+                                "Walk {} has invalid coordinates {} and {}",
+                                path_name, columns[4], columns[5]
+                            ),
+                        )?,
+                    }
+                    check_tags(&mut problems, line_number, &columns[7..])?;
+                }
+            }
+            _ => (),
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    if version.is_none() {
+        report(
+            &mut problems,
+            0,
+            "version",
+            String::from("No header with a VN:Z version tag"),
+        )?;
+    }
+
+    let total: usize = problems.counts.values().sum();
+    eprintln!("Found {} problem(s) in {}", total, file_path);
+    for (kind, count) in problems.counts.iter() {
+        eprintln!("{}\t{}", kind, count);
+    }
+    Ok(total)
+}
+
+fn report(
+    problems: &mut Problems,
+    line_number: usize,
+    kind: &'static str,
+    message: String,
+) -> io::Result<()> {
+    /*
+    Prints a problem and counts it in the summary
+     */
+    *problems.counts.entry(kind).or_insert(0) += 1;
+    writeln!(problems.writer, "{}\t{}\t{}", line_number, kind, message)
+}
+
+fn check_fields(
+    problems: &mut Problems,
+    line_number: usize,
+    columns: &[&str],
+    expected: usize,
+) -> io::Result<bool> {
+    /*
+    Checks that a line has at least the expected number of mandatory fields
+     */
+    if columns.len() < expected {
+        report(
+            problems,
+            line_number,
+            "fields",
+            format!(
+                "{}-line has {} fields, at least {} expected",
+                columns[0],
+                columns.len(),
+                expected
+            ),
+        )?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+fn check_steps(
+    problems: &mut Problems,
+    line_number: usize,
+    path_name: &str,
+    steps: &[(String, char)],
+    seq_lengths: &HashMap<String, u64>,
+    links: &HashSet<(String, char, String, char)>,
+) -> io::Result<u64> {
+    /*
+    Checks that the segments of a path exist, and that consecutive steps are linked
+    Returns the length of the sequence spelled by the path
+     */
+    let mut path_length: u64 = 0;
+    if steps.is_empty() {
+        report(
+            problems,
+            line_number,
+            "empty",
+            format!("Path {} has no steps", path_name),
+        )?;
+    }
+    for (index, (node, orientation)) in steps.iter().enumerate() {
+        match seq_lengths.get(node) {
+            Some(length) => path_length += length,
+            None => report(
+                problems,
+                line_number,
+                "missing",
+                format!("Path {} references unknown segment {}", path_name, node),
+            )?,
+        }
+        if index > 0 {
+            let (previous, previous_orientation) = &steps[index - 1];
+            if !is_linked(links, previous, *previous_orientation, node, *orientation) {
+                report(
+                    problems,
+                    line_number,
+                    "link",
+                    format!(
+                        "Path {} goes from {}{} to {}{} without a link",
+                        path_name, previous, previous_orientation, node, orientation
+                    ),
+                )?;
+            }
+        }
+    }
+    Ok(path_length)
+}
+
+fn read_p_steps(
+    problems: &mut Problems,
+    line_number: usize,
+    path_name: &str,
+    path: &str,
+) -> io::Result<Vec<(String, char)>> {
+    /*
+    Reads the steps of a P-line, reporting steps that are not a segment followed by '+' or '-'
+     */
+    let mut steps: Vec<(String, char)> = Vec::new();
+    for step in path.split(',') {
+        match step.char_indices().last() {
+            Some((end, orientation @ ('+' | '-'))) if end > 0 => {
+                steps.push((step[..end].to_string(), orientation))
+            }
+            _ => report(
+                problems,
+                line_number,
+                "step",
+                format!("Path {} has an invalid step '{}'", path_name, step),
+            )?,
+        }
+    }
+    Ok(steps)
+}
+
+fn read_w_steps(
+    problems: &mut Problems,
+    line_number: usize,
+    path_name: &str,
+    walk: &str,
+) -> io::Result<Vec<(String, char)>> {
+    /*
+    Reads the steps of a W-line as P-line steps, reporting walks that do not start with
+    an orientation ('>' or '<') and orientations that are not followed by a segment
+     */
+    if !walk.starts_with(['>', '<']) {
+        report(
+            problems,
+            line_number,
+            "step",
+            format!("Walk {} does not start with '>' or '<'", path_name),
+        )?;
+        return Ok(Vec::new());
+    }
+    let mut steps: Vec<(String, char)> = Vec::new();
+    for (node, orientation) in parse_w_steps(walk)? {
+        if node.is_empty() {
+            report(
+                problems,
+                line_number,
+                "step",
+                format!("Walk {} has an orientation without segment", path_name),
+            )?;
+        } else {
+            steps.push((node, if orientation == '>' { '+' } else { '-' }));
+        }
+    }
+    Ok(steps)
+}
+
+fn is_linked(
+    links: &HashSet<(String, char, String, char)>,
+    from: &str,
+    from_orientation: char,
+    to: &str,
+    to_orientation: char,
+) -> bool {
+    /*
+    A link can be traversed in both directions: a+ -> b- is the same as b+ -> a-
+     */
+    let flip = |orientation: char| if orientation == '+' { '-' } else { '+' };
+    links.contains(&(
+        from.to_string(),
+        from_orientation,
+        to.to_string(),
+        to_orientation,
+    )) || links.contains(&(
+        to.to_string(),
+        flip(to_orientation),
+        from.to_string(),
+        flip(from_orientation),
+    ))
+}
+
+fn parse_orientation(orientation: &str) -> Option<char> {
+    match orientation {
+        "+" => Some('+'),
+        "-" => Some('-'),
+        _ => None,
+    }
+}
+
+fn check_tags(problems: &mut Problems, line_number: usize, tags: &[&str]) -> io::Result<()> {
+    /*
+    Checks that optional fields follow the TAG:TYPE:VALUE syntax, and that values match their type
+     */
+    for tag in tags.iter() {
+        if !is_valid_tag(tag) {
+            report(problems, line_number, "tag", format!("Invalid tag {}", tag))?;
+        }
+    }
+    Ok(())
+}
+
+fn is_valid_tag(tag: &str) -> bool {
+    let fields: Vec<&str> = tag.splitn(3, ':').collect();
+    if fields.len() != 3 {
+        return false;
+    }
+    let (name, kind, value) = (fields[0].as_bytes(), fields[1], fields[2]);
+    if name.len() != 2 || !name[0].is_ascii_alphabetic() || !name[1].is_ascii_alphanumeric() {
+        return false;
+    }
+    match kind {
+        "A" => value.len() == 1 && value.bytes().all(|c| c.is_ascii_graphic()),
+        "i" => is_integer(value),
+        "f" => value.parse::<f64>().is_ok(),
+        "Z" | "J" => value.bytes().all(|c| c == b' ' || c.is_ascii_graphic()),
+        "H" => value.len() % 2 == 0 && value.bytes().all(|c| c.is_ascii_hexdigit()),
+        "B" => {
+            let mut values = value.split(',');
+            match values.next() {
+                Some("f") => values.all(|v| v.parse::<f64>().is_ok()),
+                Some("c") | Some("C") | Some("s") | Some("S") | Some("i") | Some("I") => {
+                    values.all(is_integer)
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+fn is_integer(value: &str) -> bool {
+    /*
+    An integer with at most one sign, [-+]?[0-9]+
+     */
+    let digits: &str = value.strip_prefix(['-', '+']).unwrap_or(value);
+    !digits.is_empty() && digits.bytes().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_file;
+
+    #[test]
+    fn test_is_valid_tag() {
+        assert!(is_valid_tag("LN:i:42"));
+        assert!(is_valid_tag("SN:Z:chr1 from HG1"));
+        assert!(is_valid_tag("dc:f:1.5e3"));
+        assert!(is_valid_tag("cv:B:i,1,-2,3"));
+        assert!(!is_valid_tag("LN:i:4.2"));
+        assert!(!is_valid_tag("LN:42"));
+        assert!(!is_valid_tag("LEN:i:42"));
+        assert!(!is_valid_tag("xx:H:ABC"));
+        assert!(is_valid_tag("xx:i:-5"));
+        assert!(!is_valid_tag("xx:i:--5"));
+        assert!(!is_valid_tag("cv:B:i,1,+-2"));
+    }
+
+    #[test]
+    fn test_is_linked() {
+        let mut links: HashSet<(String, char, String, char)> = HashSet::new();
+        links.insert((String::from("1"), '+', String::from("2"), '-'));
+        assert!(is_linked(&links, "1", '+', "2", '-'));
+        assert!(is_linked(&links, "2", '+', "1", '-'));
+        assert!(!is_linked(&links, "1", '+', "2", '+'));
+    }
+
+    #[test]
+    fn test_write_validation() {
+        let file_path: String = temp_file("test_write_validation.gfa");
+        std::fs::write(
+            &file_path,
+            "H\tVN:Z:1.0\nS\t1\tACGT\tLN:i:4\nS\t2\tGG\txx:i:--5\nL\t1\t+\t2\t+\t0M\n\
+             P\tp1\t1+,2+,3\t*\nP\tp2\t1,2+\t*\nW\ts1\t1\tchr1\t0\t6\t>1>2\n",
+        )
+        .unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        let total: usize = write_validation(&file_path, &mut output).unwrap();
+        std::fs::write(
+            &file_path,
+            "H\tVN:Z:1.1\nS\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tp1\t1+,2+\t*\n",
+        )
+        .unwrap();
+        let valid_total: usize = write_validation(&file_path, &mut Vec::new()).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+
+        // Malformed steps are reported instead of being dropped or glued to the next step
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "# LineNumber\tKind\tProblem\n\
+             3\ttag\tInvalid tag xx:i:--5\n\
+             5\tstep\tPath p1 has an invalid step '3'\n\
+             6\tstep\tPath p2 has an invalid step '1'\n\
+             7\tversion\tW-lines require GFA1.1 or later, but header is VN:Z:1.0\n"
+        );
+        // A non-zero total makes the command exit with status 1
+        assert_eq!(total, 4);
+        assert_eq!(valid_total, 0);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::bubbles::{
+    find_bubbles, flip, format_node, format_walk, is_reverse, load_graph, node_length,
+    path_traversals, Bubble, Graph, Traversals,
+};
+use crate::sequence::reverse_complement;
+use crate::walks::split_pansn;
+use indexmap::IndexMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+pub fn vcf_gfa(file_path: &str, reference: &str) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    write_vcf(file_path, reference, &mut writer)?;
+    writer.flush()
+}
+
+fn write_vcf<W: Write>(file_path: &str, reference: &str, writer: &mut W) -> io::Result<()> {
+    /*
+    This function reads a GFA file and prints a VCF of the bubbles crossed by the reference path
+    - REF is the sequence of the traversal of the reference, ALT the sequences of the other
+      traversals taken by paths; alleles are anchored on the last base of the bubble source
+    - genotypes are given per sample, with one haplotype per PanSN haplotype (or W-line haplotype)
+      of the sample; paths that are not named following PanSN are a sample with a single haplotype
+      The reference is genotyped too, along with the other haplotypes of its sample
+    - INFO fields give the nesting level of the bubble (LV) and the traversal of each allele (AT)
+    Alleles are uppercase, and IUPAC codes other than A, C, G and T are written as N, as VCF requires
+     */
+    let graph: Graph = load_graph(file_path)?;
+    let sequences: Vec<String> = read_sequences(file_path)?;
+    let reference_index: usize = graph
+        .paths
+        .iter()
+        .position(|(name, _)| name == reference)
+        .ok_or_else(|| {
+            eprintln!("Reference {} not found in graph", reference);
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Path not found in graph: {}", reference),
+            )
+        })?;
+    let bubbles: Vec<Bubble> = find_bubbles(&graph);
+    let traversals: Vec<Traversals> = path_traversals(&graph, &bubbles);
+
+    // Positions of each step of the reference
+    let reference_nodes: &Vec<u32> = &graph.paths[reference_index].1;
+    let mut offsets: Vec<u64> = Vec::with_capacity(reference_nodes.len());
+    let mut position: u64 = 0;
+    for node in reference_nodes.iter() {
+        offsets.push(position);
+        position += node_length(&graph, *node);
+    }
+    let (chromosome, start): (String, u64) = match split_pansn(reference) {
+        Some(pansn) => (
+            pansn.contig.to_string(),
+            pansn.range.map(|(start, _)| start).unwrap_or(0),
+        ),
+        None => (reference.to_string(), 0),
+    };
+
+    // Samples and their haplotypes, each path being one haplotype of a sample
+    let mut samples: IndexMap<String, Vec<String>> = IndexMap::new();
+    let mut haplotype_of_path: Vec<(usize, usize)> = Vec::new();
+    for (name, _) in graph.paths.iter() {
+        let (sample, haplotype) = sample_of(name);
+        let sample_entry = samples.entry(sample.to_string());
+        let sample_index: usize = sample_entry.index();
+        let haplotypes: &mut Vec<String> = sample_entry.or_default();
+        let haplotype_index: usize = match haplotypes.iter().position(|h| h == haplotype) {
+            Some(index) => index,
+            None => {
+                haplotypes.push(haplotype.to_string());
+                haplotypes.len() - 1
+            }
+        };
+        haplotype_of_path.push((sample_index, haplotype_index));
+    }
+
+    writeln!(writer, "##fileformat=VCFv4.2")?;
+    writeln!(writer, "##source=rs-pancat-paths")?;
+    writeln!(
+        writer,
+        "##contig=<ID={},length={}>",
+        chromosome,
+        start + position
+    )?;
+    writeln!(
+        writer,
+        "##INFO=<ID=LV,Number=1,Type=Integer,Description=\"Nesting level of the bubble\">"
+    )?;
+    writeln!(writer, "##INFO=<ID=AT,Number=R,Type=String,Description=\"Traversal of each allele, from source to sink\">")?;
+    writeln!(
+        writer,
+        "##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">"
+    )?;
+    write!(
+        writer,
+        "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT"
+    )?;
+    for sample in samples.keys() {
+        write!(writer, "\t{}", sample)?;
+    }
+    writeln!(writer)?;
+
+    let mut records: Vec<(u64, String)> = Vec::new();
+    for (index, bubble) in bubbles.iter().enumerate() {
+        // The first crossing of the bubble by the reference gives its position
+        let Some((reference_traversal, step)) =
+            traversals[index]
+                .iter()
+                .find_map(|(traversal, occurences)| {
+                    occurences
+                        .iter()
+                        .find(|(path, _)| *path == reference_index)
+                        .map(|(_, step)| (traversal, *step))
+                })
+        else {
+            continue;
+        };
+        // Alleles are written in the orientation of the reference
+        let reverse: bool = reference_nodes[step] != bubble.source;
+        let oriented = |traversal: &[u32]| -> Vec<u32> {
+            if reverse {
+                traversal.iter().rev().map(|node| flip(*node)).collect()
+            } else {
+                traversal.to_vec()
+            }
+        };
+        let (entry, exit) = if reverse {
+            (flip(bubble.sink), flip(bubble.source))
+        } else {
+            (bubble.source, bubble.sink)
+        };
+        let anchor: String = spell(&sequences, &[entry])
+            .chars()
+            .last()
+            .map(String::from)
+            .unwrap_or_default();
+
+        // Alleles are distinct sequences, the reference being the first one
+        let mut alleles: IndexMap<String, Vec<u32>> = IndexMap::new();
+        let reference_walk: Vec<u32> = oriented(reference_traversal);
+        alleles.insert(
+            anchor.clone() + &spell(&sequences, &reference_walk),
+            reference_walk,
+        );
+        let mut genotypes: Vec<Vec<Option<usize>>> = samples
+            .values()
+            .map(|haplotypes| vec![None; haplotypes.len()])
+            .collect();
+        for (traversal, occurences) in traversals[index].iter() {
+            let walk: Vec<u32> = oriented(traversal);
+            // Traversals spelling the same sequence share their allele
+            let allele_entry = alleles.entry(anchor.clone() + &spell(&sequences, &walk));
+            let allele: usize = allele_entry.index();
+            allele_entry.or_insert(walk);
+            for (path, _) in occurences.iter() {
+                let (sample, haplotype) = haplotype_of_path[*path];
+                genotypes[sample][haplotype].get_or_insert(allele);
+            }
+        }
+        if alleles.len() < 2 {
+            continue;
+        }
+
+        let pos: u64 = start + offsets[step] + node_length(&graph, entry);
+        let allele_traversals: Vec<String> = alleles
+            .values()
+            .map(|walk| {
+                let mut nodes: Vec<u32> = vec![entry];
+                nodes.extend(walk);
+                nodes.push(exit);
+                format_walk(&graph, &nodes)
+            })
+            .collect();
+        let mut record: String = format!(
+            "{}\t{}\t{}{}\t{}\t{}\t.\t.\tLV={};AT={}\tGT",
+            chromosome,
+            pos,
+            format_node(&graph, entry),
+            format_node(&graph, exit),
+            alleles.get_index(0).unwrap().0,
+            alleles
+                .keys()
+                .skip(1)
+                .cloned()
+                .collect::<Vec<String>>()
+                .join(","),
+            bubble.level,
+            allele_traversals.join(",")
+        );
+        for haplotypes in genotypes.iter() {
+            let genotype: Vec<String> = haplotypes
+                .iter()
+                .map(|allele| allele.map_or(String::from("."), |a| a.to_string()))
+                .collect();
+            record += &format!("\t{}", genotype.join("|"));
+        }
+        records.push((pos, record));
+    }
+    records.sort_by_key(|(pos, _)| *pos);
+    for (_, record) in records {
+        writeln!(writer, "{}", record)?;
+    }
+    Ok(())
+}
+
+fn sample_of(path_name: &str) -> (&str, &str) {
+    /*
+    Sample and haplotype of a path, from its PanSN name (or the path itself as a haploid sample)
+     */
+    match split_pansn(path_name) {
+        Some(pansn) => (pansn.sample, pansn.haplotype),
+        None => (path_name, "0"),
+    }
+}
+
+fn spell(sequences: &[String], nodes: &[u32]) -> String {
+    /*
+    Sequence spelled by a list of oriented nodes
+     */
+    let mut sequence: String = String::new();
+    for node in nodes.iter() {
+        let segment: &str = &sequences[(node / 2) as usize];
+        if is_reverse(*node) {
+            sequence += &reverse_complement(segment);
+        } else {
+            sequence += segment;
+        }
+    }
+    sequence
+}
+
+fn read_sequences(file_path: &str) -> io::Result<Vec<String>> {
+    /*
+    Sequences of the segments, in the order of the file, as VCF alleles: uppercase,
+    with IUPAC codes other than A, C, G and T replaced by N
+     */
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut sequences: Vec<String> = Vec::new();
+    let mut line: String = String::new();
+
+    while reader.read_line(&mut line)? > 0 {
+        if line.starts_with('S') {
+            let columns: Vec<&str> = line.trim_end().split('\t').collect();
+            sequences.push(
+                columns[2]
+                    .chars()
+                    .map(|base| match base.to_ascii_uppercase() {
+                        base @ ('A' | 'C' | 'G' | 'T') => base,
+                        _ => 'N',
+                    })
+                    .collect(),
+            );
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+    Ok(sequences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_file;
+
+    #[test]
+    fn test_write_vcf() {
+        let file_path: String = temp_file("vcf.gfa");
+        std::fs::write(
+            &file_path,
+            "S\t1\tACGT\nS\t2\tA\nS\t3\tr\nS\t4\tTT\n\
+            L\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t+\t0M\nL\t2\t+\t4\t+\t0M\nL\t3\t+\t4\t+\t0M\n\
+            P\tHG0#1#chr1\t1+,2+,4+\t*\nP\tHG0#2#chr1\t1+,3+,4+\t*\n\
+            W\tHG1\t1\tchr1\t0\t7\t>1>3>4\nW\tHG1\t2\tchr1\t0\t7\t>1>2>4\n",
+        )
+        .unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        write_vcf(&file_path, "HG0#1#chr1", &mut output).unwrap();
+        std::fs::remove_file(&file_path).unwrap();
+        let output: String = String::from_utf8(output).unwrap();
+        let records: Vec<&str> = output
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        // The bubble is anchored on the last base of 1, at position 4
+        // The lowercase IUPAC base of 3 is written as N, and the reference sample is genotyped
+        assert_eq!(
+            records,
+            vec!["chr1\t4\t>1>4\tTA\tTN\t.\t.\tLV=0;AT=>1>2>4,>1>3>4\tGT\t0|1\t1|0"]
+        );
+        assert!(
+            output.contains("#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tHG0\tHG1\n")
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor};
+
+pub fn read_next_p_step<R: BufRead>(file: &mut R) -> io::Result<Option<(String, char)>> {
+    /*
+     * Read the next step of a P-line path, until its orientation ('+' or '-') is found
+     * Returns None once the end of the path (tab, newline or end of file) is reached
+     * A step without orientation (e.g. '1,2+' or a trailing '3') is an InvalidData error
+     * file: the file to read, positioned inside the path description
+     */
+    let mut node: String = String::new();
+    loop {
+        let byte: Option<u8> = file.fill_buf()?.first().copied();
+        match byte {
+            None | Some(b'\t') | Some(b'\n') | Some(b'\r') | Some(b',') if !node.is_empty() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Path step without orientation: {}", node),
+                ));
+            }
+            None | Some(b'\t') | Some(b'\n') | Some(b'\r') => return Ok(None),
+            Some(orientation @ (b'+' | b'-')) => {
+                file.consume(1);
+                return Ok(Some((node, orientation as char)));
+            }
+            Some(b',') => file.consume(1),
+            Some(byte) => {
+                node.push(byte as char);
+                file.consume(1);
+            }
+        }
+    }
+}
+
+pub fn read_next_w_step<R: BufRead>(file: &mut R) -> io::Result<Option<(String, char)>> {
+    /*
+     * Read the next step of a W-line walk: an orientation ('>' or '<') followed by a node
+     * The node ends when the next orientation, a tab, a newline or the end of file is found
+     * Returns None once the end of the walk is reached
+     * file: the file to read, positioned on the orientation of the step
+     */
+    let orientation: char = match file.fill_buf()?.first() {
+        Some(byte) if *byte == b'>' || *byte == b'<' => *byte as char,
+        _ => return Ok(None),
+    };
+    file.consume(1);
+    let mut node: String = String::new();
+    while let Some(byte) = file.fill_buf()?.first() {
+        if matches!(byte, b'>' | b'<' | b'\t' | b'\n' | b'\r') {
+            break;
+        }
+        node.push(*byte as char);
+        file.consume(1);
+    }
+    Ok(Some((node, orientation)))
+}
+
+pub fn parse_p_steps(path: &str) -> io::Result<Vec<(String, char)>> {
+    /*
+    Splits the path description of a P-line (e.g. '1+,2-') into (node, orientation) steps
+     */
+    let mut reader: Cursor<&[u8]> = Cursor::new(path.trim_end().as_bytes());
+    let mut steps: Vec<(String, char)> = Vec::new();
+    while let Some(step) = read_next_p_step(&mut reader)? {
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+pub fn parse_w_steps(walk: &str) -> io::Result<Vec<(String, char)>> {
+    /*
+    Splits the walk description of a W-line (e.g. '>1<2') into (node, orientation) steps
+     */
+    let mut reader: Cursor<&[u8]> = Cursor::new(walk.trim_end().as_bytes());
+    let mut steps: Vec<(String, char)> = Vec::new();
+    while let Some(step) = read_next_w_step(&mut reader)? {
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+pub struct PanSN<'a> {
+    // A PanSN name is sample#haplotype#contig, with an optional range on the contig
+    pub sample: &'a str,
+    pub haplotype: &'a str,
+    pub contig: &'a str,
+    pub range: Option<(u64, u64)>,
+}
+
+pub fn split_pansn(path_name: &str) -> Option<PanSN<'_>> {
+    /*
+    Splits a PanSN path name 'sample#haplotype#contig' into its fields
+    An optional ':start-end' suffix on the contig is returned as a range
+     */
+    let mut fields = path_name.splitn(3, '#');
+    let (sample, haplotype, contig) = (fields.next()?, fields.next()?, fields.next()?);
+    if let Some((name, range)) = contig.rsplit_once(':') {
+        if let Some((start, end)) = range.split_once('-') {
+            if let (Ok(start), Ok(end)) = (start.parse::<u64>(), end.parse::<u64>()) {
+                return Some(PanSN {
+                    sample,
+                    haplotype,
+                    contig: name,
+                    range: Some((start, end)),
+                });
+            }
+        }
+    }
+    Some(PanSN {
+        sample,
+        haplotype,
+        contig,
+        range: None,
+    })
+}
+
+pub fn convert_paths(file_path: &str, to_paths: bool) -> io::Result<()> {
+    /*
+    This function reads a GFA file and converts P-lines to W-lines (or W-lines to P-lines)
+    P-lines must be named following PanSN (sample#haplotype#contig, optionally with a ':start-end' range)
+    Lines are kept in the same order, and tags on paths and walks are preserved
+     */
+    let seq_lengths: HashMap<String, u64> = read_lengths(file_path)?;
+
+    // Paths and walks are converted a first time to check them before anything is printed
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line: String = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        if line.starts_with(['P', 'W']) {
+            convert_line(&line, &seq_lengths, to_paths)?;
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    while reader.read_line(&mut line)? > 0 {
+        println!("{}", convert_line(&line, &seq_lengths, to_paths)?);
+        line.clear(); // Clear the line buffer for the next read
+    }
+
+    Ok(())
+}
+
+fn convert_line(
+    line: &str,
+    seq_lengths: &HashMap<String, u64>,
+    to_paths: bool,
+) -> io::Result<String> {
+    /*
+    Converts a P-line to a W-line (or a W-line to a P-line), and updates the version of the header
+    Other lines are returned unchanged (without their line break)
+     */
+    let columns: Vec<&str> = line.trim_end_matches(['\n', '\r']).split('\t').collect();
+    match line.chars().next() {
+        Some('P') if !to_paths => {
+            let pansn: PanSN = split_pansn(columns[1]).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Path {} is not named as sample#haplotype#contig",
+                        columns[1]
+                    ),
+                )
+            })?;
+            let mut walk: String = String::new();
+            let mut walk_length: u64 = 0;
+            for (node, orientation) in parse_p_steps(columns[2])? {
+                walk_length += seq_lengths.get(&node).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("Segment not found in graph: {}", node),
+                    )
+                })?;
+                walk.push(if orientation == '+' { '>' } else { '<' });
+                walk += &node;
+            }
+            let (start, end) = pansn.range.unwrap_or((0, walk_length));
+            let mut fields: Vec<String> = vec![
+                String::from("W"),
+                pansn.sample.to_string(),
+                pansn.haplotype.to_string(),
+                pansn.contig.to_string(),
+                start.to_string(),
+                end.to_string(),
+                walk,
+            ];
+            fields.extend(columns.iter().skip(4).map(|tag| tag.to_string()));
+            Ok(fields.join("\t"))
+        }
+        Some('W') if to_paths => {
+            let steps: Vec<(String, char)> = parse_w_steps(columns[6])?;
+            let mut path_name: String = format!("{}#{}#{}", columns[1], columns[2], columns[3]);
+            // The range is only kept if the walk is not the whole sequence
+            if let (Ok(start), Ok(end)) = (columns[4].parse::<u64>(), columns[5].parse::<u64>()) {
+                let walk_length: Option<u64> =
+                    steps.iter().map(|(node, _)| seq_lengths.get(node)).sum();
+                if start != 0 || walk_length.is_some_and(|length| length != end) {
+                    path_name += &format!(":{}-{}", start, end);
+                }
+            }
+            let path: Vec<String> = steps
+                .iter()
+                .map(|(node, orientation)| {
+                    format!("{}{}", node, if *orientation == '>' { '+' } else { '-' })
+                })
+                .collect();
+            let mut fields: Vec<String> = vec![
+                String::from("P"),
+                path_name,
+                path.join(","),
+                String::from("*"),
+            ];
+            fields.extend(columns.iter().skip(7).map(|tag| tag.to_string()));
+            Ok(fields.join("\t"))
+        }
+        Some('H') if !to_paths => {
+            // W-lines were introduced in GFA1.1
+            Ok(line.trim_end().replace("VN:Z:1.0", "VN:Z:1.1"))
+        }
+        _ => Ok(line.trim_end_matches('\n').to_string()),
+    }
+}
+
+pub fn read_lengths(file_path: &str) -> io::Result<HashMap<String, u64>> {
+    /*
+    Given a file path, returns the sequence length of each segment
+    Segments without sequence ('*') take their length from their LN tag, or 0 without it
+     */
+    let file: File = File::open(file_path)?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut seq_lengths: HashMap<String, u64> = HashMap::new();
+    let mut line: String = String::new();
+
+    while reader.read_line(&mut line)? > 0 {
+        if line.starts_with('S') {
+            let columns: Vec<&str> = line.trim_end().split('\t').collect();
+            let length: u64 = if columns[2] == "*" {
+                columns[3..]
+                    .iter()
+                    .find_map(|tag| tag.strip_prefix("LN:i:")?.parse::<u64>().ok())
+                    .unwrap_or(0)
+            } else {
+                columns[2].len() as u64
+            };
+            seq_lengths.insert(columns[1].to_string(), length);
+        }
+        line.clear(); // Clear the line buffer for the next read
+    }
+    Ok(seq_lengths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_steps() {
+        let p_steps: Vec<(String, char)> = parse_p_steps("1+,22-,3+\n").unwrap();
+        let w_steps: Vec<(String, char)> = parse_w_steps(">1<22>3").unwrap();
+        assert_eq!(
+            p_steps,
+            w_steps
+                .iter()
+                .map(|(node, orientation)| {
+                    (node.clone(), if *orientation == '>' { '+' } else { '-' })
+                })
+                .collect::<Vec<(String, char)>>()
+        );
+        assert_eq!(p_steps[1], (String::from("22"), '-'));
+        // Steps without orientation are errors, instead of being dropped or glued to the next one
+        for path in ["1+,2+,3", "1,2+", "1+,2x,3+"] {
+            let error: io::Error = parse_p_steps(path).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_split_pansn() {
+        let pansn: PanSN = split_pansn("HG1#2#chr1:10-20").unwrap();
+        assert_eq!(
+            (pansn.sample, pansn.haplotype, pansn.contig),
+            ("HG1", "2", "chr1")
+        );
+        assert_eq!(pansn.range, Some((10, 20)));
+        assert_eq!(split_pansn("HG1#1#chr1").unwrap().range, None);
+        assert!(split_pansn("HG1#chr1").is_none());
+    }
+
+    #[test]
+    fn test_convert_line() {
+        let seq_lengths: HashMap<String, u64> =
+            HashMap::from([(String::from("1"), 4), (String::from("2"), 2)]);
+        assert_eq!(
+            convert_line("P\tHG1#1#chr1\t1+,2-\t*\tRC:i:3\n", &seq_lengths, false).unwrap(),
+            "W\tHG1\t1\tchr1\t0\t6\t>1<2\tRC:i:3"
+        );
+        assert_eq!(
+            convert_line("P\tHG1#1#chr1:10-16\t2-\t*\n", &seq_lengths, false).unwrap(),
+            "W\tHG1\t1\tchr1\t10\t16\t<2"
+        );
+        assert_eq!(
+            convert_line("W\tHG1\t1\tchr1\t0\t6\t>1<2\n", &seq_lengths, true).unwrap(),
+            "P\tHG1#1#chr1\t1+,2-\t*"
+        );
+        // A walk that is not the whole sequence keeps its range
+        assert_eq!(
+            convert_line("W\tHG1\t1\tchr1\t10\t12\t<2\n", &seq_lengths, true).unwrap(),
+            "P\tHG1#1#chr1:10-12\t2-\t*"
+        );
+        assert!(convert_line("P\tref\t1+\t*\n", &seq_lengths, false).is_err());
+        assert!(convert_line("P\tHG1#1#chr1\t3+\t*\n", &seq_lengths, false).is_err());
+    }
+}